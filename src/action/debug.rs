@@ -1,15 +1,26 @@
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration as StdDuration, Instant};
+
 use chrono::Duration;
 use clap::ArgMatches;
+use ffsend_api::action::version::{Error as VersionError, Version as ApiVersion};
 use ffsend_api::config::SEND_DEFAULT_EXPIRE_TIME;
+use ffsend_api::reqwest::{header::SERVER, Method};
+use ffsend_api::url::Url;
+use openssl::ssl::{SslConnector, SslMethod};
 use prettytable::{format::FormatBuilder, Cell, Row, Table};
 
-use crate::client::to_duration;
+use crate::client::{create_config, to_duration};
 use crate::cmd::matcher::{debug::DebugMatcher, main::MainMatcher, Matcher};
 use crate::error::ActionError;
+use crate::host::format_url;
 #[cfg(feature = "clipboard-bin")]
 use crate::util::ClipboardType;
 use crate::util::{api_version_list, features_list, format_bool, format_duration};
 
+/// The connection timeout in seconds when diagnosing, if no timeout is configured.
+const DIAGNOSE_TIMEOUT: u64 = 10;
+
 /// A file debug action.
 pub struct Debug<'a> {
     cmd_matches: &'a ArgMatches<'a>,
@@ -28,6 +39,12 @@ impl<'a> Debug<'a> {
         let matcher_main = MainMatcher::with(self.cmd_matches).unwrap();
         let matcher_debug = DebugMatcher::with(self.cmd_matches).unwrap();
 
+        // Diagnose the connection to the host instead if requested
+        if matcher_debug.diagnose() {
+            diagnose(&matcher_main, matcher_debug.host()).printstd();
+            return Ok(());
+        }
+
         // Create a table for all debug information
         let mut table = Table::new();
         table.set_format(FormatBuilder::new().padding(0, 2).build());
@@ -41,7 +58,7 @@ impl<'a> Debug<'a> {
         // The default host
         table.add_row(Row::new(vec![
             Cell::new("Host:"),
-            Cell::new(matcher_debug.host().as_str()),
+            Cell::new(&format_url(&matcher_debug.host())),
         ]));
 
         // The history file
@@ -123,3 +140,151 @@ impl<'a> Debug<'a> {
         Ok(())
     }
 }
+
+/// Diagnose the connection to the given host, and build a report table.
+///
+/// The host name is resolved, and a TCP connection is made. For HTTPS hosts a TLS handshake is
+/// done on it, reporting the negotiated protocol version and cipher. Then the response to a HEAD
+/// request is reported, and the server API version is requested.
+fn diagnose(matcher_main: &MainMatcher, host: Url) -> Table {
+    let mut table = Table::new();
+    table.set_format(FormatBuilder::new().padding(0, 2).build());
+    table.add_row(Row::new(vec![
+        Cell::new("Host:"),
+        Cell::new(&format_url(&host)),
+    ]));
+
+    // Resolve the host name
+    let name = host.host_str().unwrap_or("").to_owned();
+    let addrs: Vec<SocketAddr> = match host.port_or_known_default() {
+        Some(port) if !name.is_empty() => match (name.as_str(), port).to_socket_addrs() {
+            Ok(addrs) => {
+                let addrs: Vec<_> = addrs.collect();
+                table.add_row(Row::new(vec![
+                    Cell::new("DNS:"),
+                    Cell::new(
+                        &addrs
+                            .iter()
+                            .map(|a| a.ip().to_string())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ),
+                ]));
+                addrs
+            }
+            Err(err) => {
+                table.add_row(Row::new(vec![
+                    Cell::new("DNS:"),
+                    Cell::new(&format!("failed: {}", err)),
+                ]));
+                return table;
+            }
+        },
+        _ => {
+            table.add_row(Row::new(vec![
+                Cell::new("DNS:"),
+                Cell::new("failed: host has no name or port"),
+            ]));
+            return table;
+        }
+    };
+
+    // Connect to the first resolved address
+    let timeout = to_duration(matcher_main.timeout())
+        .unwrap_or_else(|| StdDuration::from_secs(DIAGNOSE_TIMEOUT));
+    let stream = addrs.first().and_then(|addr| {
+        let start = Instant::now();
+        let (stream, report) = match TcpStream::connect_timeout(addr, timeout) {
+            Ok(stream) => (
+                Some(stream),
+                format!("ok, {} ms", start.elapsed().as_millis()),
+            ),
+            Err(err) => (None, format!("failed: {}", err)),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new("TCP connect:"),
+            Cell::new(&report),
+        ]));
+        stream
+    });
+
+    // Do a TLS handshake on the connection for HTTPS hosts
+    if let (Some(stream), "https") = (stream, host.scheme()) {
+        table.add_row(Row::new(vec![
+            Cell::new("TLS handshake:"),
+            Cell::new(&tls_handshake(stream, &name, timeout)),
+        ]));
+    }
+
+    // Report the response to a HEAD request
+    let client = create_config(matcher_main).client(false);
+    let start = Instant::now();
+    let response = client.get(host.clone()).build().and_then(|mut request| {
+        *request.method_mut() = Method::HEAD;
+        client.execute(request)
+    });
+    table.add_row(Row::new(vec![
+        Cell::new("HEAD request:"),
+        Cell::new(&match response {
+            Ok(response) => format!(
+                "{}, server: {}, {} ms",
+                response.status(),
+                response
+                    .headers()
+                    .get(SERVER)
+                    .and_then(|server| server.to_str().ok())
+                    .unwrap_or("unknown"),
+                start.elapsed().as_millis(),
+            ),
+            Err(err) => format!("failed: {}", err),
+        }),
+    ]));
+
+    // Request the server API version
+    let client = create_config(matcher_main).client(false);
+    let start = Instant::now();
+    table.add_row(Row::new(vec![
+        Cell::new("API request:"),
+        Cell::new(&match ApiVersion::new(host).invoke(&client) {
+            Ok(version) => format!("ok, API v{}, {} ms", version, start.elapsed().as_millis()),
+            Err(VersionError::Unknown) => format!(
+                "ok, unknown API version, {} ms",
+                start.elapsed().as_millis()
+            ),
+            Err(err) => format!("failed: {}", err),
+        }),
+    ]));
+
+    table
+}
+
+/// Do a TLS handshake with the host `name` on the given connection, and build a report.
+///
+/// The report holds the negotiated protocol version and cipher, or the reason it failed.
+fn tls_handshake(stream: TcpStream, name: &str, timeout: StdDuration) -> String {
+    let connector = match SslConnector::builder(SslMethod::tls()) {
+        Ok(builder) => builder.build(),
+        Err(err) => return format!("failed: {}", err),
+    };
+    if let Err(err) = stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|_| stream.set_write_timeout(Some(timeout)))
+    {
+        return format!("failed: {}", err);
+    }
+
+    let start = Instant::now();
+    match connector.connect(name, stream) {
+        Ok(stream) => format!(
+            "ok, {}, {}, {} ms",
+            stream.ssl().version_str(),
+            stream
+                .ssl()
+                .current_cipher()
+                .map(|cipher| cipher.name())
+                .unwrap_or("unknown cipher"),
+            start.elapsed().as_millis(),
+        ),
+        Err(err) => format!("failed: {}", err),
+    }
+}
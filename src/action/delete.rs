@@ -2,6 +2,7 @@ use clap::ArgMatches;
 use ffsend_api::action::delete::{Delete as ApiDelete, Error as DeleteError};
 use ffsend_api::file::remote_file::{FileParseError, RemoteFile};
 
+use super::check_owner;
 use crate::client::create_config;
 use crate::cmd::matcher::{delete::DeleteMatcher, main::MainMatcher, Matcher};
 use crate::error::ActionError;
@@ -42,6 +43,11 @@ impl<'a> Delete<'a> {
         // Ensure the owner token is set
         ensure_owner_token(file.owner_token_mut(), &matcher_main, false);
 
+        // Verify the owner token before modifying the file if requested
+        if matcher_delete.check_owner() {
+            check_owner(&client, &file)?;
+        }
+
         // Send the file deletion request
         let result = ApiDelete::new(&file, None).invoke(&client);
         if let Err(DeleteError::Expired) = result {
@@ -1,32 +1,63 @@
+#[cfg(unix)]
+extern crate libc;
+
+#[cfg(not(unix))]
+use std::env;
 use std::env::current_dir;
+use std::fs;
 use std::fs::create_dir_all;
-#[cfg(feature = "archive")]
-use std::io::Error as IoError;
-use std::path::{self, PathBuf};
+use std::fs::File;
+use std::io::{self, Error as IoError, ErrorKind, Write};
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{self, Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+#[cfg(unix)]
+use std::thread;
+use std::time::Instant;
 
 use clap::ArgMatches;
 use failure::Fail;
 use ffsend_api::action::download::{Download as ApiDownload, Error as DownloadError};
 use ffsend_api::action::exists::{Error as ExistsError, Exists as ApiExists};
-use ffsend_api::action::metadata::{Error as MetadataError, Metadata as ApiMetadata};
+use ffsend_api::action::metadata::{
+    Error as MetadataError, Metadata as ApiMetadata, MetadataResponse,
+};
 use ffsend_api::action::version::Error as VersionError;
+use ffsend_api::api::Version as ApiVersion;
+use ffsend_api::client::ClientConfig;
+use ffsend_api::crypto::b64;
 use ffsend_api::file::remote_file::{FileParseError, RemoteFile};
 use ffsend_api::pipe::ProgressReporter;
-#[cfg(feature = "archive")]
-use tempfile::{Builder as TempBuilder, NamedTempFile};
+use ffsend_api::url::Url;
+use infer::Infer;
+use tempfile::Builder as TempBuilder;
+use tempfile::NamedTempFile;
 
-use super::select_api_version;
+use super::{format_transfer_summary, retry, select_api_version};
 #[cfg(feature = "archive")]
 use crate::archive::archive::Archive;
 use crate::client::create_config;
 use crate::cmd::matcher::{download::DownloadMatcher, main::MainMatcher, Matcher};
+#[cfg(feature = "clipboard")]
+use crate::config::CLIPBOARD_SIZE_MAX;
 #[cfg(feature = "history")]
 use crate::history_tool;
-use crate::progress::ProgressBar;
+use crate::host::{
+    check_https, parse_share_url, take_owner_token, warn_plaintext, HostError, ShareUrlError,
+};
+use crate::logging::redact;
+use crate::metrics::{self, Direction};
+use crate::progress::{self, ProgressBar, StallSignal, WatchError};
+use crate::split::{self, Error as SplitError};
+use crate::util::print_warning;
+#[cfg(feature = "clipboard")]
+use crate::util::set_clipboard;
 use crate::util::{
-    ensure_enough_space, ensure_password, follow_url, print_error, prompt_yes, quit, quit_error,
-    quit_error_msg, ErrorHints,
+    check_enough_space, ensure_enough_space, ensure_password, follow_url, format_bytes,
+    print_error, print_json, prompt_yes, quit, sanitize_component, FollowError,
 };
 
 /// A file download action.
@@ -43,6 +74,88 @@ impl<'a> Download<'a> {
     /// Invoke the download action.
     // TODO: create a trait for this method
     pub fn invoke(&self) -> Result<(), Error> {
+        let matcher_download = DownloadMatcher::with(self.cmd_matches).unwrap();
+
+        // Download all URLs listed in a file, or the given URL
+        match matcher_download.from_file() {
+            Some(path) => self.download_all(&path),
+            None => {
+                // Count a failed download in the transfer metrics
+                let result =
+                    self.download(matcher_download.url(), matcher_download.password(), false);
+                if result.is_err() {
+                    metrics::record_failures(1);
+                }
+                result
+            }
+        }
+    }
+
+    /// Download all share URLs listed in the file at the given path, one per line.
+    ///
+    /// Blank lines and lines starting with `#` are skipped. Lines that fail to parse or download
+    /// are reported with their line number, after which the remaining URLs are downloaded. A
+    /// summary is reported at the end. Nothing is prompted for while downloading, see `download`.
+    fn download_all(&self, path: &Path) -> Result<(), Error> {
+        let matcher_main = MainMatcher::with(self.cmd_matches).unwrap();
+        let matcher_download = DownloadMatcher::with(self.cmd_matches).unwrap();
+        let data = fs::read_to_string(path).map_err(Error::FromFile)?;
+
+        // Get the password once, it is used for all files
+        let password = matcher_download.password();
+
+        let urls = parse_url_list(&data);
+        let total = urls.len();
+        let mut failed = 0;
+        for (line, url) in urls {
+            let url = match url {
+                Ok(url) => url,
+                Err(err) => {
+                    print_error(
+                        err.context(format!("invalid share URL on line {}, skipping", line)),
+                    );
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            // Refuse plaintext share URLs unless allowed, like any other share URL
+            if let Err(err) = check_https(&url, matcher_main.allow_http()) {
+                print_error(err.context(format!("refusing share URL on line {}, skipping", line)));
+                failed += 1;
+                continue;
+            }
+            warn_plaintext(&url);
+
+            if let Err(err) = self.download(url, password.clone(), true) {
+                print_error(err.context(format!("failed to download share URL on line {}", line)));
+                failed += 1;
+            }
+        }
+
+        // Report a summary of all downloads
+        metrics::record_failures(failed);
+        if !matcher_main.quiet() {
+            eprintln!(
+                "Downloaded {} of {} share URLs, {} failed",
+                total - failed,
+                total,
+                failed,
+            );
+        }
+
+        if failed > 0 {
+            return Err(Error::FromFileFailed(failed));
+        }
+        Ok(())
+    }
+
+    /// Download the file at the given share URL.
+    ///
+    /// In `batch` mode, nothing is prompted for and the program never quits. Problems that would
+    /// otherwise be prompted for, such as a missing password or an existing output file, are
+    /// returned as error instead.
+    fn download(&self, url: Url, mut password: Option<String>, batch: bool) -> Result<(), Error> {
         // Create the command matchers
         let matcher_main = MainMatcher::with(self.cmd_matches).unwrap();
         let matcher_download = DownloadMatcher::with(self.cmd_matches).unwrap();
@@ -51,16 +164,21 @@ impl<'a> Download<'a> {
         let client_config = create_config(&matcher_main);
         let client = client_config.clone().client(false);
 
-        // Get the share URL, attempt to follow it
-        let url = matcher_download.url();
-        let url = match follow_url(&client, &url) {
+        // Attempt to follow the share URL
+        let url = match follow_url(&client, &url, matcher_main.allow_http()) {
             Ok(url) => url,
+            Err(FollowError::Host(err)) => return Err(Error::Host(err)),
             Err(err) => {
                 print_error(err.context("failed to follow share URL, ignoring").compat());
                 url
             }
         };
 
+        // Ensure the share URL has a valid secret, use the given secret if it has none
+        let url = with_secret(url, matcher_download.secret())?;
+
+        info!("downloading {}", redact(&url));
+
         // Guess the host
         let host = matcher_download.guess_host(Some(url.clone()));
 
@@ -72,12 +190,16 @@ impl<'a> Download<'a> {
         // Parse the remote file based on the share URL
         let file = RemoteFile::parse_url(url, None)?;
 
-        // Get the target file or directory, and the password
+        // Get the target file or directory
         let target = matcher_download.output();
-        let mut password = matcher_download.password();
 
         // Check whether the file exists
         let exists = ApiExists::new(&file).invoke(&client)?;
+        debug!(
+            "file exists: {}, password required: {}",
+            exists.exists(),
+            exists.requires_password(),
+        );
         if !exists.exists() {
             // Remove the file from the history manager if it does not exist
             #[cfg(feature = "history")]
@@ -86,7 +208,10 @@ impl<'a> Download<'a> {
             return Err(Error::Expired);
         }
 
-        // Ensure a password is set when required
+        // Ensure a password is set when required, never prompt for it in batch mode
+        if batch && exists.requires_password() && password.is_none() {
+            return Err(Error::PasswordRequired);
+        }
         ensure_password(
             &mut password,
             exists.requires_password(),
@@ -96,6 +221,157 @@ impl<'a> Download<'a> {
 
         // Fetch the file metadata
         let metadata = ApiMetadata::new(&file, password.clone(), false).invoke(&client)?;
+        debug!(
+            "metadata: size: {} B, type: {}",
+            metadata.size(),
+            metadata.metadata().mime(),
+        );
+
+        // Download and reassemble the parts if this is a split file manifest
+        if split::is_manifest(metadata.metadata().name()) {
+            let transfer_client = client_config.clone().client(true);
+            let manifest = split::fetch_manifest(
+                &transfer_client,
+                api_version,
+                &file,
+                password.clone(),
+                metadata,
+            )?;
+            let manifest_url = file.download_url(false);
+
+            // Reassemble while downloading, and stream it into the given command or to stdout
+            if matcher_download.pipe().is_some() || matcher_download.stdout() {
+                let stall = StallSignal::default();
+                let progress =
+                    progress::reporter(ProgressBar::new_download(), &matcher_main, &stall);
+                pipe_output(matcher_download.pipe(), |mut output| {
+                    split::download(
+                        &client_config,
+                        progress,
+                        &stall,
+                        api_version,
+                        &manifest,
+                        &manifest_url,
+                        &mut output,
+                        password,
+                        &matcher_main,
+                    )
+                    .map_err(Error::from)
+                })?;
+
+                // Add the file to the history
+                #[cfg(feature = "history")]
+                history_tool::add(&matcher_main, file, true);
+
+                return Ok(());
+            }
+
+            // Prepare the target path, ensure there is enough disk space available
+            let target = expand_output(&target, manifest.name(), None, manifest.size());
+            let target = Self::prepare_path(
+                &target,
+                manifest.name(),
+                &matcher_main,
+                true,
+                matcher_download.rename_on_conflict(),
+                batch,
+            )?;
+            ensure_space(
+                target.parent().unwrap(),
+                manifest.size(),
+                &matcher_main,
+                batch,
+            )?;
+
+            // Reassemble into a temporary file next to the target, only keep it when complete
+            let mut tmp_target = TempBuilder::new()
+                .prefix(&format!(".{}-split-", crate_name!()))
+                .tempfile_in(target.parent().unwrap())
+                .map_err(Error::Output)?;
+
+            // Create a progress reporter
+            let stall = StallSignal::default();
+            let progress = progress::reporter(ProgressBar::new_download(), &matcher_main, &stall);
+
+            let start = Instant::now();
+            split::download(
+                &client_config,
+                progress,
+                &stall,
+                api_version,
+                &manifest,
+                &manifest_url,
+                tmp_target.as_file_mut(),
+                password,
+                &matcher_main,
+            )?;
+            tmp_target
+                .persist(&target)
+                .map_err(|err| Error::Output(err.error))?;
+
+            // Report a summary of the transfer
+            metrics::record(Direction::Download, manifest.size(), start.elapsed());
+            if !matcher_main.quiet() {
+                eprintln!(
+                    "Downloaded {}",
+                    format_transfer_summary(manifest.size(), start.elapsed()),
+                );
+            } else if matcher_main.json() {
+                print_json(&DownloadOutput {
+                    path: &target,
+                    name: manifest.name(),
+                    size: manifest.size(),
+                });
+            }
+
+            // Add the file to the history
+            #[cfg(feature = "history")]
+            history_tool::add(&matcher_main, file, true);
+
+            return Ok(());
+        }
+
+        // Stream the file into the given command or to stdout while downloading
+        if matcher_download.pipe().is_some() || matcher_download.stdout() {
+            #[cfg(not(unix))]
+            ensure_space(&env::temp_dir(), metadata.size(), &matcher_main, batch)?;
+
+            let stall = StallSignal::default();
+            let progress = progress::reporter(ProgressBar::new_download(), &matcher_main, &stall);
+            pipe_output(matcher_download.pipe(), |output| {
+                relay(output, |path| {
+                    // Never retry, part of the file may already have been passed on
+                    transfer(
+                        &client_config,
+                        progress,
+                        &stall,
+                        api_version,
+                        &file,
+                        path,
+                        password,
+                        metadata,
+                        0,
+                    )
+                })
+            })?;
+
+            // Add the file to the history
+            #[cfg(feature = "history")]
+            history_tool::add(&matcher_main, file, true);
+
+            return Ok(());
+        }
+
+        // Check whether to copy the file contents to the clipboard, warn if not possible
+        #[cfg(feature = "clipboard")]
+        let mut clip = matcher_download.clip();
+        #[cfg(feature = "clipboard")]
+        {
+            if clip && !can_clip(metadata.metadata().mime(), metadata.size()) {
+                print_warning("file is too large or not textual, not copying it to the clipboard");
+                clip = false;
+            }
+        }
 
         // A temporary archive file, only used when archiving
         // The temporary file is stored here, to ensure it's lifetime exceeds the upload process
@@ -108,8 +384,8 @@ impl<'a> Download<'a> {
 
         #[cfg(feature = "archive")]
         {
-            // Ask to extract if downloading an archive
-            if !extract && metadata.metadata().is_archive() {
+            // Ask to extract if downloading an archive, don't prompt in batch mode
+            if !extract && !batch && metadata.metadata().is_archive() {
                 if prompt_yes(
                     "You're downloading an archive, extract it into the selected directory?",
                     Some(true),
@@ -125,14 +401,21 @@ impl<'a> Download<'a> {
         let output_dir = !extract;
         #[cfg(not(feature = "archive"))]
         let output_dir = false;
+        let target = expand_output(
+            &target,
+            metadata.metadata().name(),
+            Some(metadata.metadata().mime()),
+            metadata.size(),
+        );
         #[allow(unused_mut)]
         let mut target = Self::prepare_path(
             &target,
             metadata.metadata().name(),
             &matcher_main,
             output_dir,
-        );
-        #[cfg(feature = "archive")]
+            matcher_download.rename_on_conflict(),
+            batch,
+        )?;
         let output_path = target.clone();
 
         #[cfg(feature = "archive")]
@@ -157,25 +440,94 @@ impl<'a> Download<'a> {
         }
 
         // Ensure there is enough disk space available when not being forced
-        if !matcher_main.force() {
-            ensure_enough_space(target.parent().unwrap(), metadata.size());
-        }
-
-        // Create a progress bar reporter
-        let progress_bar = Arc::new(Mutex::new(ProgressBar::new_download()));
-        let progress_reader: Arc<Mutex<ProgressReporter>> = progress_bar;
+        ensure_space(
+            target.parent().unwrap(),
+            metadata.size(),
+            &matcher_main,
+            batch,
+        )?;
 
-        // Create a transfer client
-        let transfer_client = client_config.client(true);
+        // Create a progress reporter
+        let stall = StallSignal::default();
+        let progress = progress::reporter(ProgressBar::new_download(), &matcher_main, &stall);
+        debug!(
+            "reader: decrypting to {}, progress reporting: {}",
+            target.display(),
+            progress.is_some(),
+        );
 
         // Execute an download action
-        let progress = if !matcher_main.quiet() {
-            Some(progress_reader)
-        } else {
-            None
-        };
-        ApiDownload::new(api_version, &file, target, password, false, Some(metadata))
-            .invoke(&transfer_client, progress)?;
+        let size = metadata.size();
+        let mime = metadata.metadata().mime().to_owned();
+        let name = metadata.metadata().name().to_owned();
+        let start = Instant::now();
+        transfer(
+            &client_config,
+            progress,
+            &stall,
+            api_version,
+            &file,
+            target.clone(),
+            password,
+            metadata,
+            matcher_main.retries(),
+        )?;
+
+        // Report a summary of the transfer
+        info!(
+            "downloaded file {} in {} ms",
+            file.id(),
+            start.elapsed().as_millis()
+        );
+        metrics::record(Direction::Download, size, start.elapsed());
+        if !matcher_main.quiet() {
+            eprintln!(
+                "Downloaded {}",
+                format_transfer_summary(size, start.elapsed()),
+            );
+        }
+
+        // Detect the file type from it's contents, compare it to the type in the metadata
+        if matcher_download.detect_type() {
+            match Infer::new().get_from_path(&target) {
+                Ok(Some(detected)) => {
+                    if !matcher_main.quiet() {
+                        eprintln!("Detected file type: {}", detected.mime);
+                    }
+                    if mime_mismatch(&mime, &detected.mime) {
+                        print_warning(format!(
+                            "the detected file type '{}' differs from the type '{}' it was shared as",
+                            detected.mime, mime,
+                        ));
+                    }
+                }
+                Ok(None) => {
+                    if !matcher_main.quiet() {
+                        eprintln!("Detected file type: unknown");
+                    }
+                }
+                Err(err) => print_error(err.context("failed to detect file type, ignoring")),
+            }
+        }
+
+        // Copy the downloaded file contents to the clipboard
+        #[cfg(feature = "clipboard")]
+        {
+            if clip {
+                match fs::read_to_string(&target) {
+                    Ok(content) => {
+                        if let Err(err) = set_clipboard(content) {
+                            print_error(
+                                err.context("failed to copy the file to the clipboard, ignoring"),
+                            );
+                        }
+                    }
+                    Err(err) => print_error(
+                        err.context("failed to read file to copy to the clipboard, ignoring"),
+                    ),
+                }
+            }
+        }
 
         // Extract the downloaded file if working with an archive
         #[cfg(feature = "archive")]
@@ -185,11 +537,24 @@ impl<'a> Download<'a> {
 
                 // Extract the downloaded file
                 Archive::new(tmp_archive.unwrap().into_file())
-                    .extract(output_path)
+                    .extract(
+                        &output_path,
+                        matcher_download.max_extract_size(),
+                        matcher_download.max_extract_entries(),
+                    )
                     .map_err(ExtractError::Extract)?;
             }
         }
 
+        // Report the downloaded file in JSON output mode
+        if matcher_main.json() {
+            print_json(&DownloadOutput {
+                path: &output_path,
+                name: &name,
+                size,
+            });
+        }
+
         // Add the file to the history
         #[cfg(feature = "history")]
         history_tool::add(&matcher_main, file, true);
@@ -213,23 +578,34 @@ impl<'a> Download<'a> {
     /// This method will check whether a file is overwitten, and whether
     /// parent directories must be created.
     ///
-    /// The program will quit with an error message if a problem occurs.
+    /// An error is returned if a problem occurs. In `batch` mode, an existing file is never
+    /// overwritten without forcing, and parent directories are created without prompting.
     fn prepare_path(
         target: &PathBuf,
         name_hint: &str,
         main_matcher: &MainMatcher,
         file: bool,
-    ) -> PathBuf {
-        // Select the path to use
-        let mut target = Self::select_path(&target, name_hint);
+        rename_on_conflict: bool,
+        batch: bool,
+    ) -> Result<PathBuf, Error> {
+        // Select the path to use, never let the server provided name escape the target directory
+        let mut target = Self::select_path(&target, &sanitize_component(name_hint))?;
 
         // Use the parent directory, if we don't want a file
         if !file {
             target = target.parent().unwrap().to_path_buf();
         }
 
-        // Ask to overwrite
+        // Pick a free file name if renaming on conflict
+        if file && rename_on_conflict {
+            target = free_path(&target);
+        }
+
+        // Ask to overwrite, refuse in batch mode
         if file && target.exists() && !main_matcher.force() {
+            if batch {
+                return Err(Error::TargetExists(target.to_string_lossy().into()));
+            }
             eprintln!(
                 "The path '{}' already exists",
                 target.to_str().unwrap_or("?"),
@@ -243,18 +619,15 @@ impl<'a> Download<'a> {
         {
             // Get the deepest directory, as we have to ensure it exists
             let dir = if file {
-                match target.parent() {
-                    Some(parent) => parent,
-                    None => quit_error_msg("invalid output file path", ErrorHints::default()),
-                }
+                target.parent().ok_or(Error::InvalidTarget)?
             } else {
                 &target
             };
 
             // Ensure the directory exists
             if !dir.is_dir() {
-                // Prompt to create them if not forced
-                if !main_matcher.force() {
+                // Prompt to create them if not forced or in batch mode
+                if !main_matcher.force() && !batch {
                     eprintln!(
                         "The directory '{}' doesn't exists",
                         dir.to_str().unwrap_or("?"),
@@ -266,16 +639,11 @@ impl<'a> Download<'a> {
                 }
 
                 // Create the parent directories
-                if let Err(err) = create_dir_all(dir) {
-                    quit_error(
-                        err.context("failed to create parent directories for output file"),
-                        ErrorHints::default(),
-                    );
-                }
+                create_dir_all(dir).map_err(Error::CreateDir)?;
             }
         }
 
-        target
+        Ok(target)
     }
 
     /// This methods prepares a full file path to use for the file to
@@ -285,27 +653,18 @@ impl<'a> Download<'a> {
     /// If no file name was given, the original file name is used.
     ///
     /// The full path including the file name will be returned.
-    fn select_path(target: &PathBuf, name_hint: &str) -> PathBuf {
+    fn select_path(target: &PathBuf, name_hint: &str) -> Result<PathBuf, Error> {
         // If we're already working with a file, canonicalize and return
         if target.is_file() {
-            match target.canonicalize() {
-                Ok(target) => return target,
-                Err(err) => quit_error(
-                    err.context("failed to canonicalize target path"),
-                    ErrorHints::default(),
-                ),
-            }
+            return target.canonicalize().map_err(Error::Target);
         }
 
         // Append the name hint if this is a directory, canonicalize and return
         if target.is_dir() {
-            match target.canonicalize() {
-                Ok(target) => return target.join(name_hint),
-                Err(err) => quit_error(
-                    err.context("failed to canonicalize target path"),
-                    ErrorHints::default(),
-                ),
-            }
+            return target
+                .canonicalize()
+                .map(|target| target.join(name_hint))
+                .map_err(Error::Target);
         }
 
         // TODO: canonicalize parent if it exists
@@ -316,13 +675,9 @@ impl<'a> Download<'a> {
         // If the path is emtpy, use the working directory with the name hint
         let use_workdir = path.map(|path| path.trim().is_empty()).unwrap_or(true);
         if use_workdir {
-            match current_dir() {
-                Ok(target) => return target.join(name_hint),
-                Err(err) => quit_error(
-                    err.context("failed to determine working directory to use for the output file"),
-                    ErrorHints::default(),
-                ),
-            }
+            return current_dir()
+                .map(|target| target.join(name_hint))
+                .map_err(Error::Target);
         }
         let path = path.unwrap();
 
@@ -336,17 +691,388 @@ impl<'a> Download<'a> {
 
         // If relative, use the working directory as base
         if target.is_relative() {
-            match current_dir() {
-                Ok(workdir) => target = workdir.join(target),
-                Err(err) => quit_error(
-                    err.context("failed to determine working directory to use for the output file"),
-                    ErrorHints::default(),
-                ),
+            target = current_dir().map_err(Error::Target)?.join(target);
+        }
+
+        Ok(target)
+    }
+}
+
+/// The download result, printed in JSON output mode.
+#[derive(Serialize)]
+struct DownloadOutput<'a> {
+    /// The path the file was downloaded to.
+    path: &'a Path,
+
+    /// The name the file was shared with.
+    name: &'a str,
+
+    /// The file size in bytes.
+    size: u64,
+}
+
+/// Ensure the given share URL has a valid secret in it's fragment.
+///
+/// If the URL has no secret, the given `secret` is used instead. An error is returned if there is
+/// no secret at all, or if it isn't valid base64url.
+fn with_secret(mut url: Url, secret: Option<&str>) -> Result<Url, Error> {
+    let has_secret = url
+        .fragment()
+        .map(|f| !f.trim().is_empty())
+        .unwrap_or(false);
+    if !has_secret {
+        url.set_fragment(Some(secret.ok_or(Error::MissingSecret)?.trim()));
+    }
+
+    match url.fragment().map(b64::decode) {
+        Some(Ok(ref secret)) if !secret.is_empty() => Ok(url),
+        _ => Err(Error::InvalidSecret),
+    }
+}
+
+/// Download and decrypt the given `file` to `path`.
+///
+/// The transfer is retried up to `retries` times, and is watched for stalls raising `stall`.
+#[allow(clippy::too_many_arguments)]
+fn transfer(
+    client_config: &ClientConfig,
+    progress: Option<Arc<Mutex<dyn ProgressReporter>>>,
+    stall: &StallSignal,
+    api_version: ApiVersion,
+    file: &RemoteFile,
+    path: PathBuf,
+    password: Option<String>,
+    metadata: MetadataResponse,
+    retries: u32,
+) -> Result<(), Error> {
+    let client_config = client_config.clone();
+    let file = file.clone();
+    let mut metadata = Some(metadata);
+    progress::watch(stall, move || {
+        let client = client_config.client(true);
+        retry(retries, || {
+            debug!("building download request using API v{}", api_version);
+            let download = ApiDownload::new(
+                api_version,
+                &file,
+                path.clone(),
+                password.clone(),
+                false,
+                metadata.take(),
+            );
+            info!("executing download request");
+            download.invoke(&client, progress.clone())
+        })
+        .map_err(Error::from)
+    })
+}
+
+/// Ensure there is enough disk space available at `path` for `size` bytes, unless forced.
+///
+/// If there isn't, an error is returned in `batch` mode, otherwise the program quits.
+fn ensure_space(
+    path: &Path,
+    size: u64,
+    matcher_main: &MainMatcher,
+    batch: bool,
+) -> Result<(), Error> {
+    if matcher_main.force() {
+        return Ok(());
+    }
+    if !batch {
+        ensure_enough_space(path, size);
+        return Ok(());
+    }
+    check_enough_space(path, size)
+        .map_err(|space| Error::NoSpace(format_bytes(size), format_bytes(space)))
+}
+
+/// Parse the share URLs listed in the given file contents, one per line.
+///
+/// Blank lines and lines starting with `#` are skipped. The line number and parsed share URL are
+/// returned for each other line, the owner token is stripped from management URLs.
+fn parse_url_list(data: &str) -> Vec<(usize, Result<Url, ShareUrlError>)> {
+    data.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line, url)| {
+            let url = parse_share_url(url).map(|mut url| {
+                take_owner_token(&mut url);
+                url
+            });
+            (line, url)
+        })
+        .collect()
+}
+
+/// Stream a downloaded file into the given shell command, or to stdout if `None`.
+///
+/// `download` is given the writer to stream the decrypted file into while it's downloaded. Writes
+/// block while the command isn't reading, so the download keeps pace with the command. The file
+/// can't be verified as a whole before it's passed on, so the command is killed if the download
+/// fails, though it may have processed part of the file by then.
+///
+/// If the command stops reading early, the download is stopped and the exit status of the
+/// command decides the result. An error is returned if the command couldn't be run, or if it
+/// exited unsuccessfully.
+fn pipe_output<F>(command: Option<&str>, download: F) -> Result<(), Error>
+where
+    F: FnOnce(PipeWriter) -> Result<(), Error>,
+{
+    let mut child = match command {
+        Some(command) => Some(spawn_command(command)?),
+        None => None,
+    };
+    let output: Box<dyn Write + Send> = match child {
+        Some(ref mut child) => Box::new(child.stdin.take().unwrap()),
+        None => Box::new(io::stdout()),
+    };
+
+    let closed = Arc::new(AtomicBool::new(false));
+    let result = download(PipeWriter {
+        inner: output,
+        closed: closed.clone(),
+    });
+    let closed = closed.load(Ordering::SeqCst);
+
+    let mut child = match child {
+        Some(child) => child,
+        // Stop quietly if whatever reads stdout stopped reading early
+        None if closed => return Ok(()),
+        None => {
+            return result.map_err(|err| match err {
+                Error::Pipe(err) => Error::Stdout(err),
+                err => err,
+            })
+        }
+    };
+    if result.is_err() && !closed {
+        let _ = child.kill();
+        let _ = child.wait();
+        return result;
+    }
+
+    let status = child.wait().map_err(Error::Pipe)?;
+    if !status.success() {
+        return Err(Error::PipeStatus(status.code().unwrap_or(-1)));
+    }
+
+    Ok(())
+}
+
+/// Spawn the given shell command, with a pipe to write to it's standard input.
+fn spawn_command(command: &str) -> Result<Child, Error> {
+    #[cfg(not(windows))]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    };
+
+    cmd.stdin(Stdio::piped()).spawn().map_err(Error::Pipe)
+}
+
+/// A writer to stream a downloaded file into, which tracks whether the reading end was closed.
+///
+/// SIGPIPE is ignored by Rust programs, so writing to a closed pipe fails with a broken pipe
+/// error instead.
+struct PipeWriter {
+    inner: Box<dyn Write + Send>,
+    closed: Arc<AtomicBool>,
+}
+
+impl PipeWriter {
+    /// Track whether the given write result means the reading end was closed.
+    fn check<T>(&self, result: io::Result<T>) -> io::Result<T> {
+        if let Err(ref err) = result {
+            if err.kind() == ErrorKind::BrokenPipe {
+                self.closed.store(true, Ordering::SeqCst);
             }
         }
+        result
+    }
+}
 
-        target
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.inner.write(buf);
+        self.check(result)
     }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.inner.flush();
+        self.check(result)
+    }
+}
+
+/// Download a file into `output`, with `download` downloading it to a path.
+///
+/// The API only downloads to a path, so it's given the path of an anonymous pipe in `/dev/fd`,
+/// which is relayed into `output` on a separate thread.
+#[cfg(unix)]
+fn relay<F>(mut output: PipeWriter, download: F) -> Result<(), Error>
+where
+    F: FnOnce(PathBuf) -> Result<(), Error>,
+{
+    let (mut reader, writer) = anonymous_pipe().map_err(Error::Pipe)?;
+    let relay =
+        thread::spawn(move || io::copy(&mut reader, &mut output).and_then(|_| output.flush()));
+
+    download(PathBuf::from(format!("/dev/fd/{}", writer.as_raw_fd())))?;
+
+    // Close the writing end, so the relay reaches the end of the pipe
+    drop(writer);
+    relay
+        .join()
+        .expect("failed to relay downloaded file")
+        .map_err(Error::Pipe)
+}
+
+/// Download a file into `output`, with `download` downloading it to a path.
+///
+/// The file is downloaded into a temporary file first, as it can't be streamed on this platform.
+/// The temporary file is deleted when dropped, which also happens when returning an error.
+#[cfg(not(unix))]
+fn relay<F>(mut output: PipeWriter, download: F) -> Result<(), Error>
+where
+    F: FnOnce(PathBuf) -> Result<(), Error>,
+{
+    let tmp = TempBuilder::new()
+        .prefix(&format!(".{}-pipe-", crate_name!()))
+        .tempfile()
+        .map_err(Error::Pipe)?;
+    download(tmp.path().to_path_buf())?;
+
+    let mut input = File::open(tmp.path()).map_err(Error::Pipe)?;
+    io::copy(&mut input, &mut output).map_err(Error::Pipe)?;
+    Ok(())
+}
+
+/// Create an anonymous pipe, returning it's reading and writing end.
+///
+/// Both ends are closed on exec, so spawned commands don't keep the pipe open.
+#[cfg(unix)]
+fn anonymous_pipe() -> Result<(File, File), IoError> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(IoError::last_os_error());
+    }
+    let pipe = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+    for fd in &fds {
+        if unsafe { libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+            return Err(IoError::last_os_error());
+        }
+    }
+
+    Ok(pipe)
+}
+
+/// Expand placeholders for file metadata fields in the given output path.
+///
+/// Supported placeholders are `{name}`, `{stem}`, `{ext}`, `{mime}`, `{type}` and `{size}`, where
+/// `{type}` is the top-level MIME type such as `text`. Unknown placeholders are kept as-is. Every
+/// expanded value is sanitized to a single path component, so metadata can't escape the chosen
+/// directory.
+fn expand_output(target: &Path, name: &str, mime: Option<&str>, size: u64) -> PathBuf {
+    let template = match target.to_str() {
+        Some(template) if template.contains('{') => template,
+        _ => return target.to_path_buf(),
+    };
+
+    // Collect the field values
+    let name_path = Path::new(name);
+    let mime = mime
+        .and_then(|mime| mime.split(';').next())
+        .map(|mime| mime.trim())
+        .filter(|mime| !mime.is_empty())
+        .unwrap_or("application/octet-stream");
+    let size = size.to_string();
+    let ext = name_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let fields = [
+        ("{name}", name),
+        (
+            "{stem}",
+            name_path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or(name),
+        ),
+        ("{ext}", ext),
+        ("{mime}", mime),
+        ("{type}", mime.split('/').next().unwrap_or(mime)),
+        ("{size}", size.as_str()),
+    ];
+
+    // Drop the separator before a missing extension, file names can't end in a dot everywhere
+    let template = if ext.is_empty() {
+        template.replace(".{ext}", "{ext}")
+    } else {
+        template.to_owned()
+    };
+
+    // Empty values such as a missing extension are kept empty, rather than sanitized to `_`
+    let path = fields.iter().fold(template, |path, (key, value)| {
+        let value = if value.is_empty() {
+            String::new()
+        } else {
+            sanitize_component(value)
+        };
+        path.replace(key, &value)
+    });
+    PathBuf::from(path)
+}
+
+/// Find a free path for the given file path, by appending a counter to the file name.
+///
+/// If the path doesn't exist yet, it's returned as-is. Otherwise the first free path in the form
+/// of `name (1).ext`, `name (2).ext` and so on is returned, preserving the extension.
+fn free_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+
+    // Split the file name in a stem and extension
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("file")
+        .to_owned();
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| format!(".{}", e))
+        .unwrap_or_default();
+
+    // Increment the counter until a free path is found
+    (1..)
+        .map(|i| path.with_file_name(format!("{} ({}){}", stem, i, ext)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// Check whether the given detected MIME type mismatches the MIME type from the file metadata.
+///
+/// MIME type parameters are ignored. A generic binary type in the metadata never mismatches, as
+/// it's used when the uploader couldn't determine the type.
+fn mime_mismatch(mime: &str, detected: &str) -> bool {
+    let mime = mime.split(';').next().unwrap_or("").trim().to_lowercase();
+    !mime.is_empty() && mime != "application/octet-stream" && mime != detected.to_lowercase()
+}
+
+/// Check whether a file with the given MIME type and size may be copied to the clipboard.
+///
+/// Only small textual files are allowed, see `CLIPBOARD_SIZE_MAX`.
+#[cfg(feature = "clipboard")]
+fn can_clip(mime: &str, size: u64) -> bool {
+    size <= CLIPBOARD_SIZE_MAX && (mime.starts_with("text/") || mime == "application/json")
 }
 
 #[derive(Debug, Fail)]
@@ -378,9 +1104,83 @@ pub enum Error {
     #[fail(display = "failed the extraction procedure")]
     Extract(#[cause] ExtractError),
 
+    /// An error occurred while downloading the parts of a split file.
+    #[fail(display = "failed to download split file")]
+    Split(#[cause] SplitError),
+
     /// The given Send file has expired, or did never exist in the first place.
     #[fail(display = "the file has expired or did never exist")]
     Expired,
+
+    /// Failed to read the file listing share URLs to download.
+    #[fail(display = "failed to read file with share URLs")]
+    FromFile(#[cause] IoError),
+
+    /// Some of the share URLs listed in a file failed to download.
+    #[fail(display = "failed to download {} of the listed share URLs", _0)]
+    FromFileFailed(usize),
+
+    /// The share URL doesn't include the file secret, and no secret was given.
+    #[fail(display = "the share link has no secret after '#', specify it with '--secret'")]
+    MissingSecret,
+
+    /// The file secret is not valid base64url.
+    #[fail(display = "the file secret is malformed, it must be base64url encoded")]
+    InvalidSecret,
+
+    /// Failed to pipe the downloaded file into the given command.
+    #[fail(display = "failed to pipe downloaded file into command")]
+    Pipe(#[cause] IoError),
+
+    /// Failed to write the downloaded file to stdout.
+    #[fail(display = "failed to write downloaded file to stdout")]
+    Stdout(#[cause] IoError),
+
+    /// The share URL uses a host that may not be used.
+    #[fail(display = "refusing the share URL")]
+    Host(#[cause] HostError),
+
+    /// A password is required, but wasn't given.
+    #[fail(display = "the file is password protected, but no password was given")]
+    PasswordRequired,
+
+    /// The output file already exists.
+    #[fail(display = "the output file already exists: {}", _0)]
+    TargetExists(String),
+
+    /// The output file path is invalid.
+    #[fail(display = "invalid output file path")]
+    InvalidTarget,
+
+    /// Failed to determine the output file path.
+    #[fail(display = "failed to determine the output file path")]
+    Target(#[cause] IoError),
+
+    /// Failed to create the parent directories of the output file.
+    #[fail(display = "failed to create parent directories for output file")]
+    CreateDir(#[cause] IoError),
+
+    /// There isn't enough disk space available for the file.
+    #[fail(
+        display = "not enough disk space available, {} required but only {} is available",
+        _0, _1
+    )]
+    NoSpace(String, String),
+
+    /// The download stalled, or ended unexpectedly.
+    #[fail(display = "failed to download the file")]
+    Watch(#[cause] WatchError),
+
+    /// Failed to write the downloaded file to it's target.
+    #[fail(display = "failed to write the downloaded file")]
+    Output(#[cause] IoError),
+
+    /// The command the downloaded file was piped into exited unsuccessfully.
+    #[fail(
+        display = "the command the file was piped into exited with status {}",
+        _0
+    )]
+    PipeStatus(i32),
 }
 
 impl From<VersionError> for Error {
@@ -413,6 +1213,18 @@ impl From<DownloadError> for Error {
     }
 }
 
+impl From<WatchError> for Error {
+    fn from(err: WatchError) -> Self {
+        Error::Watch(err)
+    }
+}
+
+impl From<SplitError> for Error {
+    fn from(err: SplitError) -> Error {
+        Error::Split(err)
+    }
+}
+
 #[cfg(feature = "archive")]
 impl From<ExtractError> for Error {
     fn from(err: ExtractError) -> Error {
@@ -431,3 +1243,239 @@ pub enum ExtractError {
     #[fail(display = "failed to extract archive contents to target directory")]
     Extract(#[cause] IoError),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn select_path_traversal() {
+        let dir = tempdir().unwrap();
+        let base = dir.path().canonicalize().unwrap();
+        for name in &[
+            "../../etc/passwd",
+            "..",
+            "/etc/passwd",
+            "..\\..\\boot.ini",
+            "C:\\x",
+        ] {
+            let path = Download::select_path(&dir.path().to_path_buf(), &sanitize_component(name))
+                .unwrap();
+            assert_eq!(path.parent(), Some(base.as_path()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn parse_url_list_lines() {
+        let data = "\
+            # Files to download\n\
+            https://send.example.com/download/abc/#secret\n\
+            \n   \n\
+            ftp://send.example.com/download/def/#secret\n\
+            \t# https://send.example.com/download/ghi/#secret\n\
+            https://send.example.com/download/jkl/#secret&owner=token\n";
+        let urls = parse_url_list(data);
+
+        let lines: Vec<usize> = urls.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![2, 5, 7]);
+        assert_eq!(
+            urls[0].1.as_ref().unwrap().as_str(),
+            "https://send.example.com/download/abc/#secret",
+        );
+        assert!(urls[1].1.is_err());
+        assert_eq!(
+            urls[2].1.as_ref().unwrap().as_str(),
+            "https://send.example.com/download/jkl/#secret",
+        );
+    }
+
+    #[test]
+    fn parse_url_list_empty() {
+        assert!(parse_url_list("").is_empty());
+        assert!(parse_url_list("\n# nothing here\n\n").is_empty());
+    }
+
+    #[test]
+    fn expand_output_fields() {
+        let path = expand_output(
+            Path::new("out/{type}/{stem}-{size}.{ext}"),
+            "report.pdf",
+            Some("application/pdf; charset=binary"),
+            42,
+        );
+        assert_eq!(path, Path::new("out/application/report-42.pdf"));
+        let path = expand_output(Path::new("{mime}/{name}"), "a.txt", Some("text/plain"), 1);
+        assert_eq!(path, Path::new("text_plain/a.txt"));
+    }
+
+    #[test]
+    fn expand_output_plain() {
+        let path = expand_output(Path::new("out/file.bin"), "report.pdf", None, 42);
+        assert_eq!(path, Path::new("out/file.bin"));
+        let path = expand_output(Path::new("out/{unknown}"), "report.pdf", None, 42);
+        assert_eq!(path, Path::new("out/{unknown}"));
+    }
+
+    #[test]
+    fn expand_output_defaults() {
+        let path = expand_output(Path::new("{type}/{stem}.{ext}"), "Makefile", None, 0);
+        assert_eq!(path, Path::new("application/Makefile"));
+        let path = expand_output(Path::new("{stem}.{ext}.bak"), ".bashrc", None, 0);
+        assert_eq!(path, Path::new(".bashrc.bak"));
+        let path = expand_output(Path::new("{mime}"), "a", Some(" ; x"), 0);
+        assert_eq!(path, Path::new("application_octet-stream"));
+    }
+
+    #[test]
+    fn expand_output_traversal() {
+        let path = expand_output(Path::new("out/{name}"), "../../etc/passwd", None, 0);
+        assert_eq!(path, Path::new("out/.._.._etc_passwd"));
+        let path = expand_output(Path::new("out/{name}"), "..", None, 0);
+        assert_eq!(path, Path::new("out/_"));
+    }
+
+    #[test]
+    fn free_path_unused() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("report.pdf");
+        assert_eq!(free_path(&path), path);
+    }
+
+    #[test]
+    fn free_path_counter() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("report.pdf"), b"").unwrap();
+        fs::write(dir.path().join("report (1).pdf"), b"").unwrap();
+        assert_eq!(
+            free_path(&dir.path().join("report.pdf")),
+            dir.path().join("report (2).pdf"),
+        );
+    }
+
+    #[test]
+    fn free_path_extensions() {
+        let dir = tempdir().unwrap();
+        for (name, free) in &[
+            ("Makefile", "Makefile (1)"),
+            (".bashrc", ".bashrc (1)"),
+            ("archive.tar.gz", "archive.tar (1).gz"),
+        ] {
+            fs::write(dir.path().join(name), b"").unwrap();
+            assert_eq!(free_path(&dir.path().join(name)), dir.path().join(free));
+        }
+    }
+
+    #[test]
+    fn mime_mismatch_same() {
+        assert!(!mime_mismatch("image/png", "image/png"));
+        assert!(!mime_mismatch("Image/PNG", "image/png"));
+        assert!(!mime_mismatch("text/plain; charset=utf-8", "text/plain"));
+    }
+
+    #[test]
+    fn mime_mismatch_different() {
+        assert!(mime_mismatch("image/png", "application/pdf"));
+        assert!(mime_mismatch("text/plain", "application/x-executable"));
+    }
+
+    #[test]
+    fn mime_mismatch_generic() {
+        assert!(!mime_mismatch("application/octet-stream", "image/png"));
+        assert!(!mime_mismatch("", "image/png"));
+        assert!(!mime_mismatch(" ; charset=utf-8", "image/png"));
+    }
+
+    #[cfg(feature = "clipboard")]
+    #[test]
+    fn can_clip_text() {
+        assert!(can_clip("text/plain", 0));
+        assert!(can_clip("text/markdown", CLIPBOARD_SIZE_MAX));
+        assert!(can_clip("application/json", 1024));
+    }
+
+    #[cfg(feature = "clipboard")]
+    #[test]
+    fn can_clip_refused() {
+        assert!(!can_clip("text/plain", CLIPBOARD_SIZE_MAX + 1));
+        assert!(!can_clip("image/png", 1024));
+        assert!(!can_clip("application/octet-stream", 1024));
+    }
+
+    #[test]
+    fn download_output_json() {
+        let output = DownloadOutput {
+            path: Path::new("/tmp/report.pdf"),
+            name: "report.pdf",
+            size: 1024,
+        };
+        assert_eq!(
+            serde_json::to_string(&output).unwrap(),
+            r#"{"path":"/tmp/report.pdf","name":"report.pdf","size":1024}"#,
+        );
+    }
+
+    /// Build a shell command writing it's input to the file at `path`, uppercased.
+    #[cfg(unix)]
+    fn upper_into(path: &Path) -> String {
+        format!("tr a-z A-Z > '{}'", path.display())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipe_command_output() {
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("out");
+        pipe_output(Some(&upper_into(&out)), |mut output| {
+            output.write_all(b"hello").map_err(Error::Pipe)
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "HELLO");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipe_command_relay() {
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("out");
+        let data = "abc".repeat(1024 * 1024);
+        pipe_output(Some(&upper_into(&out)), |output| {
+            relay(output, |path| fs::write(path, &data).map_err(Error::Output))
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), data.to_uppercase());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipe_command_closed_early() {
+        let data = vec![0; 4 * 1024 * 1024];
+        pipe_output(Some("head -c 1 > /dev/null"), |output| {
+            relay(output, |path| fs::write(path, &data).map_err(Error::Output))
+        })
+        .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipe_command_failed() {
+        match pipe_output(Some("exit 3"), |mut output| {
+            output.write_all(b"hello").map_err(Error::Pipe)
+        }) {
+            Err(Error::PipeStatus(3)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipe_download_failed() {
+        let dir = tempdir().unwrap();
+        let out = dir.path().join("out");
+        match pipe_output(Some(&upper_into(&out)), |_| Err(Error::InvalidTarget)) {
+            Err(Error::InvalidTarget) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
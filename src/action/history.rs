@@ -1,11 +1,25 @@
+use std::fs;
+
 use clap::ArgMatches;
 use failure::Fail;
+use ffsend_api::action::exists::Exists as ApiExists;
+use ffsend_api::crypto::b64;
+use ffsend_api::file::remote_file::RemoteFile;
 use prettytable::{format::FormatBuilder, Cell, Row, Table};
 
+use crate::client::create_config;
 use crate::cmd::matcher::{history::HistoryMatcher, main::MainMatcher, Matcher};
 use crate::error::ActionError;
 use crate::history::{History as HistoryManager, LoadError as HistoryLoadError};
-use crate::util::{format_duration, quit_error, quit_error_msg, ErrorHintsBuilder};
+use crate::util::{
+    format_duration, print_error, print_warning, quit_error, quit_error_msg, ErrorHintsBuilder,
+};
+
+/// The length in bytes of a valid file secret.
+const SECRET_LENGTH: usize = 16;
+
+/// The problem reported for entries duplicating the ID of an earlier entry.
+const DUPLICATE_ID: &str = "duplicate ID";
 
 /// A history action.
 pub struct History<'a> {
@@ -25,8 +39,36 @@ impl<'a> History<'a> {
         let matcher_main = MainMatcher::with(self.cmd_matches).unwrap();
         let matcher_history = HistoryMatcher::with(self.cmd_matches).unwrap();
 
-        // Get the history path, make sure it exists
+        // Get the history path
         let history_path = matcher_main.history();
+
+        // Import history exported on another machine
+        if let Some(path) = matcher_history.import() {
+            let data = match fs::read_to_string(&path) {
+                Ok(data) => data,
+                Err(err) => quit_error(
+                    err.context("failed to read history file to import"),
+                    ErrorHintsBuilder::default().verbose(false).build().unwrap(),
+                ),
+            };
+
+            // Merge the imported files into the history, and save
+            let mut history = HistoryManager::load_or_new(history_path)?;
+            let count = history.import(&data, matcher_history.replace())?;
+            if let Err(err) = history.save() {
+                quit_error(
+                    err,
+                    ErrorHintsBuilder::default().verbose(true).build().unwrap(),
+                );
+            }
+
+            if !matcher_main.quiet() {
+                eprintln!("Imported {} file(s) into history", count);
+            }
+            return Ok(());
+        }
+
+        // Make sure the history file exists
         if !history_path.is_file() {
             if !matcher_main.quiet() {
                 eprintln!("No files in history");
@@ -45,6 +87,30 @@ impl<'a> History<'a> {
             return Ok(());
         }
 
+        // Export the history
+        if matcher_history.export() {
+            match history.export() {
+                Ok(data) => print!("{}", data),
+                Err(err) => quit_error(
+                    err.context("failed to export history"),
+                    ErrorHintsBuilder::default().verbose(true).build().unwrap(),
+                ),
+            }
+
+            if !matcher_main.quiet() {
+                print_warning(
+                    "the exported history contains secrets and owner tokens, keep it private",
+                );
+            }
+            return Ok(());
+        }
+
+        // Check the integrity of all history entries
+        if matcher_history.check() {
+            Self::check(&mut history, &matcher_main, &matcher_history);
+            return Ok(());
+        }
+
         // Clear all history
         if matcher_history.clear() {
             history.clear();
@@ -143,6 +209,128 @@ impl<'a> History<'a> {
 
         Ok(())
     }
+
+    /// Check the integrity of all entries in the given history, and report problems.
+    ///
+    /// If probing, the server is asked whether each file still exists. If fixing, entries having
+    /// a problem are removed from the history.
+    fn check(
+        history: &mut HistoryManager,
+        matcher_main: &MainMatcher,
+        matcher_history: &HistoryMatcher,
+    ) {
+        let client = create_config(matcher_main).client(false);
+
+        // Find the problem for each entry
+        let files = history.files();
+        let problems: Vec<(usize, RemoteFile, &str)> = files
+            .iter()
+            .enumerate()
+            .filter_map(|(i, file)| {
+                let problem = entry_problem(file)
+                    .or_else(|| duplicate_problem(file, &files[..i]))
+                    .or_else(|| {
+                        if !matcher_history.probe() {
+                            return None;
+                        }
+                        match ApiExists::new(file).invoke(&client) {
+                            Ok(exists) if !exists.exists() => Some("no longer exists"),
+                            Ok(_) => None,
+                            Err(err) => {
+                                print_error(err.context("failed to probe history entry, ignoring"));
+                                None
+                            }
+                        }
+                    });
+                problem.map(|problem| (i, file.clone(), problem))
+            })
+            .collect();
+
+        if problems.is_empty() {
+            if !matcher_main.quiet() {
+                eprintln!("All {} history entries are valid", history.files().len());
+            }
+            return;
+        }
+
+        // Report the problems, or just the URLs in quiet mode
+        if !matcher_main.quiet() {
+            let mut table = Table::new();
+            table.set_format(FormatBuilder::new().padding(0, 2).build());
+            table.add_row(Row::new(vec![Cell::new("LINK"), Cell::new("PROBLEM")]));
+            for (_, file, problem) in &problems {
+                table.add_row(Row::new(vec![
+                    Cell::new(file.download_url(true).as_str()),
+                    Cell::new(problem),
+                ]));
+            }
+            table.printstd();
+        } else {
+            problems
+                .iter()
+                .for_each(|(_, file, _)| println!("{}", file.download_url(true)));
+        }
+
+        if !matcher_history.fix() {
+            return;
+        }
+
+        // Remove just the entries having a problem, keeping the first of duplicate IDs, and save
+        let indices: Vec<usize> = problems.iter().map(|(i, _, _)| *i).collect();
+        let removed = history.remove_at(&indices);
+        if let Err(err) = history.save() {
+            quit_error(
+                err,
+                ErrorHintsBuilder::default().verbose(true).build().unwrap(),
+            );
+        }
+
+        if !matcher_main.quiet() {
+            eprintln!("Removed {} entries from history", removed);
+        }
+    }
+}
+
+/// Find whether the given history entry duplicates the ID or owner token of any of the given
+/// `previous` entries.
+fn duplicate_problem(file: &RemoteFile, previous: &[RemoteFile]) -> Option<&'static str> {
+    if previous.iter().any(|f| f.id() == file.id()) {
+        return Some(DUPLICATE_ID);
+    }
+    if let Some(token) = file.owner_token() {
+        if previous.iter().any(|f| f.owner_token() == Some(token)) {
+            return Some("owner token shared with another entry");
+        }
+    }
+    None
+}
+
+/// Find a structural problem with the given history entry, if any.
+///
+/// This checks whether the secret decodes to a key of the right length, and whether the owner
+/// token is intact. Expired entries are reported as well.
+fn entry_problem(file: &RemoteFile) -> Option<&'static str> {
+    let secret_valid = file
+        .download_url(true)
+        .fragment()
+        .and_then(|secret| b64::decode(secret).ok())
+        .map(|secret| secret.len() == SECRET_LENGTH)
+        .unwrap_or(false);
+    if !secret_valid {
+        return Some("invalid or missing secret");
+    }
+
+    if let Some(token) = file.owner_token() {
+        if token.trim().is_empty() || !token.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Some("invalid owner token");
+        }
+    }
+
+    if file.has_expired() {
+        return Some("expired");
+    }
+
+    None
 }
 
 #[derive(Debug, Fail)]
@@ -157,3 +345,89 @@ impl From<HistoryLoadError> for ActionError {
         ActionError::History(Error::Load(err))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ffsend_api::url::Url;
+
+    /// A valid secret, encoding a key of `SECRET_LENGTH` bytes.
+    const SECRET: &str = "AAAAAAAAAAAAAAAAAAAAAA";
+
+    /// Build a history entry for the given file ID, secret and owner token.
+    fn file(id: &str, secret: &str, owner_token: Option<&str>) -> RemoteFile {
+        let url = Url::parse(&format!(
+            "https://send.example.com/download/{}/#{}",
+            id, secret
+        ))
+        .unwrap();
+        RemoteFile::parse_url(url, owner_token.map(|t| t.to_owned())).unwrap()
+    }
+
+    #[test]
+    fn entry_problem_valid() {
+        assert_eq!(entry_problem(&file("abc12345", SECRET, None)), None);
+        assert_eq!(
+            entry_problem(&file("abc12345", SECRET, Some("token123"))),
+            None
+        );
+    }
+
+    #[test]
+    fn entry_problem_secret() {
+        assert_eq!(
+            entry_problem(&file("abc12345", "c2VjcmV0", None)),
+            Some("invalid or missing secret"),
+        );
+    }
+
+    #[test]
+    fn entry_problem_owner_token() {
+        for token in &["", "  ", "token&owner=x", "tok en"] {
+            assert_eq!(
+                entry_problem(&file("abc12345", SECRET, Some(token))),
+                Some("invalid owner token"),
+                "{:?}",
+                token,
+            );
+        }
+    }
+
+    #[test]
+    fn duplicate_problem_id() {
+        let previous = vec![
+            file("abc12345", SECRET, None),
+            file("def12345", SECRET, None),
+        ];
+        assert_eq!(
+            duplicate_problem(&file("abc12345", SECRET, None), &previous),
+            Some(DUPLICATE_ID),
+        );
+        assert_eq!(
+            duplicate_problem(&file("ghi12345", SECRET, None), &previous),
+            None
+        );
+        assert_eq!(
+            duplicate_problem(&file("abc12345", SECRET, None), &[]),
+            None
+        );
+    }
+
+    #[test]
+    fn duplicate_problem_owner_token() {
+        let previous = vec![file("abc12345", SECRET, Some("token"))];
+        assert_eq!(
+            duplicate_problem(&file("def12345", SECRET, Some("token")), &previous),
+            Some("owner token shared with another entry"),
+        );
+        assert_eq!(
+            duplicate_problem(&file("def12345", SECRET, Some("other")), &previous),
+            None,
+        );
+        assert_eq!(
+            duplicate_problem(&file("def12345", SECRET, None), &previous),
+            None
+        );
+    }
+}
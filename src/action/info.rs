@@ -133,23 +133,33 @@ impl<'a> Info<'a> {
             // The download count
             table.add_row(Row::new(vec![
                 Cell::new("Downloads:"),
-                Cell::new(&format!(
-                    "{} of {}",
-                    info.download_count(),
-                    info.download_limit()
+                Cell::new(&format_downloads(
+                    Some(info.download_count()),
+                    Some(info.download_limit()),
                 )),
             ]));
 
-            // The time to live
-            let ttl_millis = info.ttl_millis() as i64;
-            let ttl = Duration::milliseconds(ttl_millis);
+            // The time to live, and the remaining downloads, whichever runs out first
             table.add_row(Row::new(vec![
                 Cell::new("Expiry:"),
-                Cell::new(&if ttl_millis >= 60 * 1000 {
-                    format!("{} ({}s)", format_duration(&ttl), ttl.num_seconds())
-                } else {
-                    format_duration(&ttl)
-                }),
+                Cell::new(&format_expiry(
+                    info.ttl_millis(),
+                    info.download_count(),
+                    info.download_limit(),
+                )),
+            ]));
+        } else {
+            // Note which fields are unavailable without the owner token
+            table.add_row(Row::new(vec![
+                Cell::new("Downloads:"),
+                Cell::new(&format!(
+                    "{} (owner token required)",
+                    format_downloads(None, None)
+                )),
+            ]));
+            table.add_row(Row::new(vec![
+                Cell::new("Expiry:"),
+                Cell::new("? (owner token required)"),
             ]));
         }
 
@@ -160,6 +170,36 @@ impl<'a> Info<'a> {
     }
 }
 
+/// Format the download count against the download limit, such as `3 / 10 used`.
+///
+/// Either field may be unknown, it is shown as `?`. If both are unknown, just `?` is returned.
+fn format_downloads(count: Option<usize>, limit: Option<usize>) -> String {
+    let field = |value: Option<usize>| value.map_or("?".into(), |v| v.to_string());
+    match (count, limit) {
+        (None, None) => "?".into(),
+        (count, limit) => format!("{} / {} used", field(count), field(limit)),
+    }
+}
+
+/// Format the expiry of a file, being the time to live in milliseconds or the remaining number
+/// of downloads, whichever runs out first.
+///
+/// For example: `1w (604800s) or after 5 more downloads`
+fn format_expiry(ttl_millis: u64, download_count: usize, download_limit: usize) -> String {
+    let ttl = Duration::milliseconds(ttl_millis as i64);
+    let downloads_left = download_limit.saturating_sub(download_count);
+    format!(
+        "{} or after {} more download{}",
+        if ttl_millis >= 60 * 1000 {
+            format!("{} ({}s)", format_duration(&ttl), ttl.num_seconds())
+        } else {
+            format_duration(&ttl)
+        },
+        downloads_left,
+        if downloads_left == 1 { "" } else { "s" },
+    )
+}
+
 #[derive(Debug, Fail)]
 pub enum Error {
     /// Failed to parse a share URL, it was invalid.
@@ -197,3 +237,58 @@ impl From<InfoError> for Error {
         Error::Info(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downloads_both() {
+        assert_eq!(format_downloads(Some(3), Some(10)), "3 / 10 used");
+        assert_eq!(format_downloads(Some(0), Some(1)), "0 / 1 used");
+    }
+
+    #[test]
+    fn downloads_one() {
+        assert_eq!(format_downloads(Some(3), None), "3 / ? used");
+        assert_eq!(format_downloads(None, Some(10)), "? / 10 used");
+    }
+
+    #[test]
+    fn downloads_neither() {
+        assert_eq!(format_downloads(None, None), "?");
+    }
+
+    #[test]
+    fn expiry_combined() {
+        assert_eq!(
+            format_expiry(7 * 24 * 60 * 60 * 1000, 0, 5),
+            "1w (604800s) or after 5 more downloads",
+        );
+        assert_eq!(
+            format_expiry(90 * 1000, 2, 3),
+            "1m30s (90s) or after 1 more download",
+        );
+    }
+
+    #[test]
+    fn expiry_short_ttl() {
+        assert_eq!(
+            format_expiry(30 * 1000, 0, 2),
+            "30s or after 2 more downloads"
+        );
+        assert_eq!(format_expiry(0, 0, 1), "now or after 1 more download");
+    }
+
+    #[test]
+    fn expiry_downloads_exhausted() {
+        assert_eq!(
+            format_expiry(30 * 1000, 5, 5),
+            "30s or after 0 more downloads"
+        );
+        assert_eq!(
+            format_expiry(30 * 1000, 7, 5),
+            "30s or after 0 more downloads"
+        );
+    }
+}
@@ -11,13 +11,26 @@ pub mod password;
 pub mod upload;
 pub mod version;
 
+use std::io::{Error as IoError, ErrorKind};
+use std::thread;
+use std::time::Duration as StdDuration;
+
+use chrono::Duration;
+use failure::Fail;
+use ffsend_api::action::info::{Error as InfoError, Info as ApiInfo};
 use ffsend_api::action::version::{Error as VersionError, Version as ApiVersion};
+use ffsend_api::api::request::ResponseError;
 use ffsend_api::api::DesiredVersion;
 use ffsend_api::client::Client;
+use ffsend_api::file::remote_file::RemoteFile;
+use ffsend_api::reqwest::{self, StatusCode};
 use ffsend_api::url::Url;
 
-use crate::config::API_VERSION_ASSUME;
-use crate::util::print_warning;
+use crate::action::info::Error as CliInfoError;
+use crate::config::{API_VERSION_ASSUME, RETRY_DELAY_BASE, RETRY_DELAY_MAX};
+use crate::error::ActionError;
+use crate::logging::redact;
+use crate::util::{format_bytes, format_duration, print_error, print_warning};
 
 /// Based on the given desired API version, select a version we can use.
 ///
@@ -37,9 +50,13 @@ fn select_api_version(
     // TODO: only lookup if `DesiredVersion::Assume` after first operation attempt failed
 
     // Look up the version
+    debug!("looking up server API version of {}", redact(&host));
     match ApiVersion::new(host).invoke(&client) {
         // Use the probed version
-        Ok(v) => *desired = DesiredVersion::Use(v),
+        Ok(v) => {
+            debug!("server API version is v{}", v);
+            *desired = DesiredVersion::Use(v);
+        }
 
         // If unknown, just assume the default version
         Err(VersionError::Unknown) => {
@@ -56,3 +73,376 @@ fn select_api_version(
 
     Ok(())
 }
+
+/// Verify the owner token of the given file, by fetching the file info using it.
+///
+/// This is used before modifying a file, to fail early with a clear error if the owner token is
+/// invalid. An expired file is not reported here, the modifying action itself reports that. Only
+/// a refused owner token is reported as invalid, other failures such as network errors are
+/// returned as is.
+fn check_owner(client: &Client, file: &RemoteFile) -> Result<(), ActionError> {
+    match ApiInfo::new(file, None).invoke(client) {
+        Ok(_) | Err(InfoError::Expired) => Ok(()),
+        Err(err) if is_owner_refused(&err) => Err(ActionError::OwnerInvalid(err)),
+        Err(err) => Err(ActionError::Info(CliInfoError::Info(err))),
+    }
+}
+
+/// Check whether the given error is caused by the server refusing the owner token.
+///
+/// This is the case if the server responded with an authorization failure. Not found is not a
+/// refused owner token, it means the file is gone.
+fn is_owner_refused(err: &dyn Fail) -> bool {
+    for cause in err.iter_chain() {
+        if let Some(err) = cause.downcast_ref::<ResponseError>() {
+            return match err {
+                ResponseError::Unauthorized => true,
+                ResponseError::OtherHttp(status, _) => {
+                    *status == reqwest::StatusCode::UNAUTHORIZED
+                        || *status == reqwest::StatusCode::FORBIDDEN
+                }
+                _ => false,
+            };
+        }
+    }
+    false
+}
+
+/// Format a summary of a finished transfer, with the number of transferred bytes, the elapsed
+/// time and the average speed.
+///
+/// For example: `12.3 MiB in 5s (2.5 MiB/s)`
+fn format_transfer_summary(bytes: u64, elapsed: StdDuration) -> String {
+    // Determine the average speed, in bytes per second
+    let millis = elapsed.as_millis();
+    let speed = if millis > 0 {
+        (u128::from(bytes) * 1000 / millis) as u64
+    } else {
+        bytes
+    };
+
+    // Format the elapsed time, show sub second transfers as such
+    let elapsed = if elapsed.as_secs() == 0 {
+        "<1s".into()
+    } else {
+        Duration::from_std(elapsed)
+            .map(format_duration)
+            .unwrap_or_else(|_| "?".into())
+    };
+
+    format!(
+        "{} in {} ({}/s)",
+        format_bytes(bytes),
+        elapsed,
+        format_bytes(speed),
+    )
+}
+
+/// Run the given transfer, retrying it up to `retries` times if it fails.
+///
+/// Only transient failures are retried, see `is_transient`. Each retried failure is reported,
+/// after a delay that doubles with every retry. The error of the last attempt is returned if all
+/// attempts failed, or right away if the failure is permanent.
+pub(crate) fn retry<T, E: Fail>(
+    retries: u32,
+    mut transfer: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let mut attempt = 0;
+    loop {
+        match transfer() {
+            Ok(result) => return Ok(result),
+            Err(err) if attempt < retries && is_transient(&err) => {
+                let delay = RETRY_DELAY_BASE
+                    .saturating_mul(1 << attempt.min(16))
+                    .min(RETRY_DELAY_MAX);
+                attempt += 1;
+                info!(
+                    "attempt {} failed with transient error (status: {}), retrying in {}s",
+                    attempt,
+                    failure_status(&err).map_or("none".into(), |s| s.to_string()),
+                    delay,
+                );
+                print_error(err.context(format!(
+                    "transfer failed, retrying in {}s ({} of {})",
+                    delay, attempt, retries,
+                )));
+                thread::sleep(StdDuration::from_secs(delay));
+            }
+            Err(err) => {
+                if attempt < retries {
+                    info!(
+                        "attempt {} failed with permanent error (status: {}), not retrying",
+                        attempt + 1,
+                        failure_status(&err).map_or("none".into(), |s| s.to_string()),
+                    );
+                } else if retries > 0 {
+                    info!("attempt {} failed, out of retries", attempt + 1);
+                }
+                return Err(err);
+            }
+        }
+    }
+}
+
+/// Find the HTTP status code of the response the given failure was caused by, if any.
+fn failure_status(err: &dyn Fail) -> Option<StatusCode> {
+    for cause in err.iter_chain() {
+        if let Some(err) = cause.downcast_ref::<ResponseError>() {
+            return match err {
+                ResponseError::OtherHttp(status, _) => Some(*status),
+                _ => None,
+            };
+        }
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            return err.status();
+        }
+    }
+    None
+}
+
+/// Check whether the given transfer failure is transient, and may succeed when retried.
+///
+/// The cause chain is walked to find the underlying failure. Network failures and server errors
+/// (5xx) are transient. Other response errors, such as an expired file or a wrong password, and
+/// any other failure are permanent.
+fn is_transient(err: &dyn Fail) -> bool {
+    for cause in err.iter_chain() {
+        if let Some(err) = cause.downcast_ref::<ResponseError>() {
+            return match err {
+                ResponseError::OtherHttp(status, _) => status.is_server_error(),
+                _ => false,
+            };
+        }
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            return err.status().map_or(true, |status| status.is_server_error());
+        }
+        if let Some(err) = cause.downcast_ref::<IoError>() {
+            return match err.kind() {
+                ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe
+                | ErrorKind::TimedOut
+                | ErrorKind::UnexpectedEof => true,
+                _ => false,
+            };
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    use ffsend_api::client::ClientConfig;
+
+    /// A transfer error wrapping some cause, like the errors of ffsend-api do.
+    #[derive(Debug, Fail)]
+    enum TransferError {
+        #[fail(display = "response error")]
+        Response(#[cause] ResponseError),
+
+        #[fail(display = "IO error")]
+        Io(#[cause] IoError),
+
+        #[fail(display = "wrong password")]
+        Password,
+    }
+
+    #[test]
+    fn transient_server_errors() {
+        for status in &[StatusCode::INTERNAL_SERVER_ERROR, StatusCode::BAD_GATEWAY] {
+            let err = TransferError::Response(ResponseError::OtherHttp(*status, String::new()));
+            assert!(is_transient(&err));
+        }
+    }
+
+    #[test]
+    fn permanent_response_errors() {
+        assert!(!is_transient(&TransferError::Response(
+            ResponseError::Expired
+        )));
+        assert!(!is_transient(&TransferError::Response(
+            ResponseError::Unauthorized
+        )));
+        for status in &[StatusCode::BAD_REQUEST, StatusCode::NOT_FOUND] {
+            let err = TransferError::Response(ResponseError::OtherHttp(*status, String::new()));
+            assert!(!is_transient(&err));
+        }
+    }
+
+    #[test]
+    fn transient_network_errors() {
+        let err = TransferError::Io(IoError::new(ErrorKind::ConnectionReset, "reset"));
+        assert!(is_transient(&err));
+        let err = TransferError::Io(IoError::new(ErrorKind::PermissionDenied, "denied"));
+        assert!(!is_transient(&err));
+    }
+
+    #[test]
+    fn permanent_other_errors() {
+        assert!(!is_transient(&TransferError::Password));
+    }
+
+    #[test]
+    fn owner_refused() {
+        assert!(is_owner_refused(&TransferError::Response(
+            ResponseError::Unauthorized
+        )));
+        for status in &[StatusCode::UNAUTHORIZED, StatusCode::FORBIDDEN] {
+            let err = TransferError::Response(ResponseError::OtherHttp(*status, String::new()));
+            assert!(is_owner_refused(&err));
+        }
+    }
+
+    #[test]
+    fn owner_not_refused() {
+        for status in &[StatusCode::NOT_FOUND, StatusCode::INTERNAL_SERVER_ERROR] {
+            let err = TransferError::Response(ResponseError::OtherHttp(*status, String::new()));
+            assert!(!is_owner_refused(&err));
+        }
+        let err = TransferError::Io(IoError::new(ErrorKind::ConnectionReset, "reset"));
+        assert!(!is_owner_refused(&err));
+        assert!(!is_owner_refused(&TransferError::Password));
+    }
+
+    #[test]
+    fn retry_permanent_once() {
+        let mut attempts = 0;
+        let result: Result<(), _> = retry(3, || {
+            attempts += 1;
+            Err(TransferError::Password)
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn transfer_summary() {
+        assert_eq!(
+            format_transfer_summary(5 << 20, StdDuration::from_secs(2)),
+            "5.00 MiB in 2s (2.50 MiB/s)",
+        );
+        assert_eq!(
+            format_transfer_summary(90 << 20, StdDuration::from_secs(90)),
+            "90.00 MiB in 1m30s (1.00 MiB/s)",
+        );
+    }
+
+    #[test]
+    fn transfer_summary_sub_second() {
+        assert_eq!(
+            format_transfer_summary(500, StdDuration::from_millis(200)),
+            "500 B in <1s (2.44 KiB/s)",
+        );
+        assert_eq!(
+            format_transfer_summary(100, StdDuration::from_secs(0)),
+            "100 B in <1s (100 B/s)",
+        );
+    }
+
+    #[test]
+    fn failure_status_cause() {
+        let err = TransferError::Response(ResponseError::OtherHttp(
+            StatusCode::BAD_GATEWAY,
+            String::new(),
+        ));
+        assert_eq!(failure_status(&err), Some(StatusCode::BAD_GATEWAY));
+        assert_eq!(
+            failure_status(&TransferError::Response(ResponseError::Expired)),
+            None
+        );
+        let err = TransferError::Io(IoError::new(ErrorKind::ConnectionReset, "reset"));
+        assert_eq!(failure_status(&err), None);
+    }
+
+    /// Build a raw HTTP response with the given status line, extra headers and body.
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body,
+        )
+    }
+
+    /// A response providing the authentication nonce for a file.
+    fn nonce() -> String {
+        response("200 OK", "WWW-Authenticate: send-v1 AAAA\r\n", "")
+    }
+
+    /// Serve the given responses on a local Send host, one per request in order.
+    ///
+    /// A file uploaded to this host is returned, with an owner token.
+    fn serve(responses: Vec<String>) -> RemoteFile {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+
+                // Read the request headers and body before responding
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim().to_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if line.starts_with("content-length:") {
+                        length = line[15..].trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let url = Url::parse(&format!("http://{}/download/abcdef123456/#c2VjcmV0", addr)).unwrap();
+        RemoteFile::parse_url(url, Some("token123".into())).unwrap()
+    }
+
+    /// Check the owner token of a file on a host serving the given responses.
+    fn check(responses: Vec<String>) -> Result<(), ActionError> {
+        let file = serve(responses);
+        check_owner(&ClientConfig::default().client(false), &file)
+    }
+
+    #[test]
+    fn check_owner_valid() {
+        let info = response("200 OK", "", r#"{"dlimit":10,"dtotal":3,"ttl":60000}"#);
+        assert!(check(vec![nonce(), info]).is_ok());
+    }
+
+    #[test]
+    fn check_owner_refused() {
+        for status in &["401 Unauthorized", "403 Forbidden"] {
+            match check(vec![nonce(), response(status, "", "")]) {
+                Err(ActionError::OwnerInvalid(_)) => {}
+                _ => panic!("expected a refused owner token for {}", status),
+            }
+        }
+    }
+
+    #[test]
+    fn check_owner_expired() {
+        assert!(check(vec![response("404 Not Found", "", "")]).is_ok());
+        assert!(check(vec![nonce(), response("404 Not Found", "", "")]).is_ok());
+    }
+
+    #[test]
+    fn check_owner_server_error() {
+        match check(vec![nonce(), response("500 Internal Server Error", "", "")]) {
+            Err(ActionError::Info(_)) => {}
+            _ => panic!("expected an info error"),
+        }
+    }
+}
@@ -3,6 +3,7 @@ use ffsend_api::action::password::{Error as PasswordError, Password as ApiPasswo
 use ffsend_api::file::remote_file::RemoteFile;
 use prettytable::{format::FormatBuilder, Cell, Row, Table};
 
+use super::check_owner;
 use crate::client::create_config;
 use crate::cmd::matcher::{main::MainMatcher, password::PasswordMatcher, Matcher};
 use crate::error::ActionError;
@@ -43,6 +44,11 @@ impl<'a> Password<'a> {
         // Ensure the owner token is set
         ensure_owner_token(file.owner_token_mut(), &matcher_main, false);
 
+        // Verify the owner token before modifying the file if requested
+        if matcher_password.check_owner() {
+            check_owner(&client, &file)?;
+        }
+
         // Get the password to use and whether it was generated
         let (password, password_generated) = matcher_password.password();
 
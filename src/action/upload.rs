@@ -1,43 +1,52 @@
 use std::env::current_dir;
-#[cfg(feature = "archive")]
-use std::io::Error as IoError;
-use std::path::Path;
-#[cfg(feature = "archive")]
-use std::path::PathBuf;
+use std::io::{self, Error as IoError};
+use std::path::{Path, PathBuf};
 #[cfg(feature = "archive")]
 use std::process::exit;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 use clap::ArgMatches;
 use failure::Fail;
+use ffsend_api::action::delete::Delete as ApiDelete;
 use ffsend_api::action::params::ParamsDataBuilder;
 use ffsend_api::action::upload::{Error as UploadError, Upload as ApiUpload};
 use ffsend_api::action::version::Error as VersionError;
+use ffsend_api::api::{DesiredVersion, Version as ApiVersion};
 use ffsend_api::config::{upload_size_max, UPLOAD_SIZE_MAX_RECOMMENDED};
-use ffsend_api::pipe::ProgressReporter;
+use ffsend_api::url::Url;
 use pathdiff::diff_paths;
 use prettytable::{format::FormatBuilder, Cell, Row, Table};
 #[cfg(feature = "qrcode")]
 use qr2term::print_qr;
-#[cfg(feature = "archive")]
 use tempfile::{Builder as TempBuilder, NamedTempFile};
 
-use super::select_api_version;
+use super::{format_transfer_summary, retry, select_api_version};
 #[cfg(feature = "archive")]
 use crate::archive::archiver::Archiver;
-use crate::client::create_config;
-use crate::cmd::matcher::{MainMatcher, Matcher, UploadMatcher};
+use crate::client::{create_config, create_webhook_config};
+use crate::cmd::matcher::{upload::NameFallback, MainMatcher, Matcher, UploadMatcher};
+use crate::config::API_VERSION_ASSUME;
+#[cfg(feature = "archive")]
+use crate::config::ARCHIVE_NAME_DEFAULT;
 #[cfg(feature = "history")]
 use crate::history_tool;
-use crate::progress::ProgressBar;
+use crate::host::{format_url, management_url};
+use crate::logging::redact;
+use crate::metrics::{self, Direction};
+use crate::progress::{self, ProgressBar, StallSignal, WatchError, STALL_POLL_MILLIS};
+use crate::split::{self, Error as SplitError};
 #[cfg(feature = "urlshorten")]
 use crate::urlshorten;
 #[cfg(feature = "clipboard")]
 use crate::util::set_clipboard;
 use crate::util::{
-    format_bytes, open_url, print_error, print_error_msg, prompt_yes, quit, quit_error_msg,
-    ErrorHintsBuilder,
+    escape_html, format_bytes, format_duration, open_url, print_error, print_error_msg, print_json,
+    prompt_yes, quit, quit_error_msg, sanitize_component, ErrorHintsBuilder,
 };
+use crate::webhook;
 
 /// A file upload action.
 pub struct Upload<'a> {
@@ -53,6 +62,16 @@ impl<'a> Upload<'a> {
     /// Invoke the upload action.
     // TODO: create a trait for this method
     pub fn invoke(&self) -> Result<(), Error> {
+        // Count a failed upload in the transfer metrics
+        let result = self.upload();
+        if result.is_err() {
+            metrics::record_failures(1);
+        }
+        result
+    }
+
+    /// Upload the selected file.
+    fn upload(&self) -> Result<(), Error> {
         // Create the command matchers
         let matcher_main = MainMatcher::with(self.cmd_matches).unwrap();
         let matcher_upload = UploadMatcher::with(self.cmd_matches).unwrap();
@@ -68,8 +87,52 @@ impl<'a> Upload<'a> {
         let host = matcher_upload.host();
 
         // The file name to use
-        #[allow(unused_mut)]
-        let mut file_name = matcher_upload.name().map(|s| s.to_owned());
+        let mut file_name = matcher_upload.name().map(sanitize_component);
+
+        // Read from stdin into a temporary file if the path is `-`
+        // The temporary file is stored here, to ensure it's lifetime exceeds the upload process
+        let mut tmp_stdin: Option<NamedTempFile> = None;
+        if paths.iter().any(|p| p == Path::new("-")) {
+            if paths.len() > 1 {
+                quit_error_msg(
+                    "uploading from stdin can't be combined with other files",
+                    ErrorHintsBuilder::default().verbose(false).build().unwrap(),
+                );
+            }
+            if file_name.is_none() {
+                quit_error_msg(
+                    "you must specify a file name when uploading from stdin",
+                    ErrorHintsBuilder::default()
+                        .name(true)
+                        .verbose(false)
+                        .build()
+                        .unwrap(),
+                );
+            }
+
+            // Explain the upload request right away, don't consume stdin just to explain it
+            if matcher_upload.explain() {
+                explain(
+                    &matcher_main,
+                    &matcher_upload,
+                    &host,
+                    &path,
+                    file_name.as_ref(),
+                )
+                .printstd();
+                return Ok(());
+            }
+
+            // Buffer stdin, as the file size must be known before uploading
+            let mut tmp_file = TempBuilder::new()
+                .prefix(&format!(".{}-stdin-", crate_name!()))
+                .tempfile()
+                .map_err(Error::Stdin)?;
+            io::copy(&mut io::stdin().lock(), tmp_file.as_file_mut()).map_err(Error::Stdin)?;
+            path = tmp_file.path().to_path_buf();
+            paths = vec![path.clone()];
+            tmp_stdin = Some(tmp_file);
+        }
 
         // All paths must exist
         // TODO: ensure the file exists and is accessible
@@ -82,6 +145,19 @@ impl<'a> Upload<'a> {
             }
         }
 
+        // Symbolic links are followed, unless the user disallowed it
+        if matcher_upload.no_follow() {
+            if let Some(path) = find_symlink(&paths) {
+                quit_error_msg(
+                    format!(
+                        "the path '{}' is a symbolic link, not following it",
+                        path.to_str().unwrap_or("?"),
+                    ),
+                    ErrorHintsBuilder::default().verbose(false).build().unwrap(),
+                );
+            }
+        }
+
         // A temporary archive file, only used when archiving
         // The temporary file is stored here, to ensure it's lifetime exceeds the upload process
         #[allow(unused_mut)]
@@ -120,7 +196,9 @@ impl<'a> Upload<'a> {
 
             // Archive the selected file or directory
             if archive {
-                eprintln!("Archiving...");
+                if !matcher_main.quiet() {
+                    eprintln!("Archiving...");
+                }
                 let archive_extention = ".tar";
 
                 // Create a new temporary file to write the archive to
@@ -141,28 +219,21 @@ impl<'a> Upload<'a> {
 
                     // Select the file name to use if not set
                     if file_name.is_none() {
-                        // Require user to specify name if multiple files are given
                         if paths.len() > 1 {
-                            quit_error_msg(
-                                "you must specify a file name for the archive",
-                                ErrorHintsBuilder::default()
-                                    .name(true)
-                                    .verbose(false)
-                                    .build()
-                                    .unwrap(),
+                            // Use a generic name if multiple files are given
+                            file_name = Some(ARCHIVE_NAME_DEFAULT.into());
+                        } else {
+                            // Derive name from given file
+                            file_name = Some(
+                                path.canonicalize()
+                                    .map_err(|err| ArchiveError::FileName(Some(err)))?
+                                    .file_name()
+                                    .ok_or(ArchiveError::FileName(None))?
+                                    .to_str()
+                                    .map(|s| s.to_owned())
+                                    .ok_or(ArchiveError::FileName(None))?,
                             );
                         }
-
-                        // Derive name from given file
-                        file_name = Some(
-                            path.canonicalize()
-                                .map_err(|err| ArchiveError::FileName(Some(err)))?
-                                .file_name()
-                                .ok_or(ArchiveError::FileName(None))?
-                                .to_str()
-                                .map(|s| s.to_owned())
-                                .ok_or(ArchiveError::FileName(None))?,
-                        );
                     }
 
                     // Get the current working directory, including working directory as highest possible root, canonicalize it
@@ -182,6 +253,8 @@ impl<'a> Upload<'a> {
 
                     // Build an archiver, append each file
                     let mut archiver = Archiver::new(archive_file);
+                    archiver.follow_symlinks(matcher_upload.dereference());
+                    archiver.exclude(matcher_upload.exclude());
                     for path in &paths {
                         // Canonicalize the path
                         let mut path = Path::new(path).to_path_buf();
@@ -236,6 +309,24 @@ impl<'a> Upload<'a> {
             }
         }
 
+        // Pick a file name if none is given, and it can't be derived from the path
+        if file_name.is_none() {
+            file_name = fallback_name(&path, matcher_upload.name_fallback())?;
+        }
+
+        // Explain the upload request, without uploading anything
+        if matcher_upload.explain() {
+            explain(
+                &matcher_main,
+                &matcher_upload,
+                &host,
+                &path,
+                file_name.as_ref(),
+            )
+            .printstd();
+            return Ok(());
+        }
+
         // Create a reqwest client capable for uploading files
         let client_config = create_config(&matcher_main);
         let client = client_config.clone().client(false);
@@ -248,11 +339,16 @@ impl<'a> Upload<'a> {
         // We do not authenticate for now
         let auth = false;
 
+        // The size of parts to split the file into, if splitting
+        let split_size = matcher_upload.split();
+
         // TODO: extract this into external function
         {
-            // Determine the max file size
+            // Determine the max file size, the server limit may be given explicitly
             // TODO: set false parameter to authentication state
-            let max_size = upload_size_max(api_version, auth);
+            let max_size = matcher_upload
+                .max_size()
+                .unwrap_or_else(|| upload_size_max(api_version, auth));
 
             // Get the file size, fail on emtpy files, warn about large files
             if let Ok(size) = path.metadata().map(|m| m.len()) {
@@ -268,8 +364,9 @@ impl<'a> Upload<'a> {
                     )
                 }
 
-                // Enforce maximum file size
-                if size > max_size && !matcher_main.force() {
+                // Enforce maximum file size, unless splitting into parts that are small enough
+                let split_fits = split_size.map(|s| s <= max_size).unwrap_or(false);
+                if size > max_size && !split_fits && !matcher_main.force() {
                     // The file is too large, show an error and quit
                     quit_error_msg(
                         format!(
@@ -286,7 +383,11 @@ impl<'a> Upload<'a> {
                 }
 
                 // Enforce maximum recommended size
-                if size > UPLOAD_SIZE_MAX_RECOMMENDED && !matcher_main.force() {
+                let split_recommended = split_size
+                    .map(|s| s <= UPLOAD_SIZE_MAX_RECOMMENDED)
+                    .unwrap_or(false);
+                if size > UPLOAD_SIZE_MAX_RECOMMENDED && !split_recommended && !matcher_main.force()
+                {
                     // The file is larger than the recommended maximum, warn
                     eprintln!(
                         "The file size is {}, bigger than the recommended maximum of {}",
@@ -307,21 +408,16 @@ impl<'a> Upload<'a> {
 
         // TODO: assert max expiry time for file
 
-        // Create a reqwest client capable for uploading files
-        let transfer_client = client_config.client(true);
-
-        // Create a progress bar reporter
-        let progress_bar = Arc::new(Mutex::new(ProgressBar::new_upload()));
+        // Get the download limit to use
+        let download_limit = matcher_upload
+            .download_limit(&matcher_main, api_version, auth)
+            .map(|d| d as u8);
 
-        // Build a parameters object to set for the file
-        let params = {
+        // Build a parameters object to set for the file, built for each upload attempt
+        let params = move || {
             // Build the parameters data object
             let params = ParamsDataBuilder::default()
-                .download_limit(
-                    matcher_upload
-                        .download_limit(&matcher_main, api_version, auth)
-                        .map(|d| d as u8),
-                )
+                .download_limit(download_limit)
                 .build()
                 .unwrap();
 
@@ -333,31 +429,157 @@ impl<'a> Upload<'a> {
             }
         };
 
-        // Build the progress reporter
-        let progress_reporter: Arc<Mutex<ProgressReporter>> = progress_bar;
-
         // Get the password to use and whether it was generated
         let password = matcher_upload.password();
         let (password, password_generated) =
             password.map(|(p, g)| (Some(p), g)).unwrap_or((None, false));
 
+        // Remember the file state, to detect the file being changed while uploading
+        let state = FileState::of(&path);
+        let size = state.as_ref().map(|state| state.size);
+
         // Execute an upload action, obtain the URL
-        let reporter = if !matcher_main.quiet() {
-            Some(&progress_reporter)
+        let stall = StallSignal::default();
+        let progress_reporter =
+            progress::reporter(ProgressBar::new_upload(), &matcher_main, &stall);
+        let reporter = progress_reporter.as_ref();
+        // The name the file is shared with
+        let name = file_name
+            .clone()
+            .or_else(|| {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .map(|n| n.to_owned())
+            })
+            .unwrap_or_else(|| "file".into());
+
+        // Log the upload metadata and reader setup, never log the password
+        info!(
+            "uploading {} as {:?} to {} using API v{}",
+            path.display(),
+            name,
+            redact(&host),
+            api_version,
+        );
+        debug!(
+            "metadata: size: {}, download limit: {}, password: {}",
+            size.map_or("unknown".into(), |s| format!("{} B", s)),
+            download_limit.map_or("none".into(), |l| l.to_string()),
+            if password.is_some() { "set" } else { "none" },
+        );
+        debug!(
+            "reader: encrypting from {}, progress reporting: {}",
+            path.display(),
+            progress_reporter.is_some(),
+        );
+
+        // Stop waiting for the upload right away if the file changes while uploading
+        let guard = FileGuard::watch(&path, &state, &stall);
+
+        let start = Instant::now();
+        let uploaded = match (split_size, size) {
+            // Split the file into multiple uploads if it's larger than the part size
+            (Some(part_size), Some(size)) if size > part_size => {
+                debug!("splitting upload into parts of {} B", part_size);
+                let upload = split::upload(
+                    &client_config,
+                    reporter,
+                    &stall,
+                    api_version,
+                    host,
+                    &path,
+                    name.clone(),
+                    part_size,
+                    password.clone(),
+                    download_limit,
+                    &matcher_main,
+                );
+                upload
+                    .map(|upload| (upload.manifest, upload.parts))
+                    .map_err(Error::from)
+            }
+            _ => {
+                // Upload on a separate thread, to stop waiting for it when it stalls
+                let client_config = client_config.clone();
+                let reporter = progress_reporter.clone();
+                let (path, file_name, password) =
+                    (path.clone(), file_name.clone(), password.clone());
+                let retries = matcher_main.retries();
+                let file = progress::watch(&stall, move || {
+                    let client = client_config.client(true);
+                    retry(retries, || {
+                        debug!("building upload request");
+                        let upload = ApiUpload::new(
+                            api_version,
+                            host.clone(),
+                            path.clone(),
+                            file_name.clone(),
+                            password.clone(),
+                            params(),
+                        );
+                        info!("executing upload request");
+                        upload.invoke(&client, reporter.as_ref())
+                    })
+                    .map_err(Error::from)
+                });
+                file.map(|file| (file, Vec::new()))
+            }
+        };
+        let (file, parts) = match uploaded {
+            Err(_) if guard.changed() => return Err(Error::FileChanged),
+            uploaded => uploaded?,
+        };
+        drop(guard);
+
+        // Track the uploaded file until it is added to the history, in case we're interrupted
+        #[cfg(feature = "history")]
+        {
+            history_tool::add_pending(&matcher_main, &file);
+            for part in &parts {
+                history_tool::add_pending(&matcher_main, part);
+            }
+        }
+
+        // The uploaded data is corrupt if the file changed, delete it along with any parts
+        if FileState::of(&path) != state {
+            #[cfg(feature = "history")]
+            {
+                history_tool::discard_pending(&file);
+                for part in &parts {
+                    history_tool::discard_pending(part);
+                }
+            }
+            if let Err(err) = ApiDelete::new(&file, None).invoke(&client) {
+                print_error(err.context("failed to delete the corrupt uploaded file, ignoring"));
+            }
+            split::delete_parts(&client, &parts);
+            return Err(Error::FileChanged);
+        }
+        info!(
+            "uploaded file {} in {} ms",
+            file.id(),
+            start.elapsed().as_millis()
+        );
+        metrics::record(Direction::Upload, size.unwrap_or(0), start.elapsed());
+
+        // Add the file to the history manager right away, so it isn't lost if we're interrupted
+        // The parts of a split file are added as well, to keep their owner tokens
+        #[cfg(feature = "history")]
+        {
+            history_tool::add(&matcher_main, file.clone(), false);
+            for part in &parts {
+                history_tool::add(&matcher_main, part.clone(), false);
+            }
+        }
+
+        // The share URL, without the secret if it is shared separately
+        let secret = if matcher_upload.no_secret_url() {
+            Some(file.secret())
         } else {
             None
         };
-        let file = ApiUpload::new(
-            api_version,
-            host,
-            path.clone(),
-            file_name,
-            password.clone(),
-            params,
-        )
-        .invoke(&transfer_client, reporter)?;
         #[allow(unused_mut)]
-        let mut url = file.download_url(true);
+        let mut url = file.download_url(secret.is_none());
 
         // Shorten the share URL if requested, prompt the user to confirm
         #[cfg(feature = "urlshorten")]
@@ -375,6 +597,13 @@ impl<'a> Upload<'a> {
             }
         }
 
+        // Build an HTML snippet linking to the file, if requested
+        let html = if matcher_upload.html() {
+            Some(html_snippet(url.as_str(), &name, download_limit))
+        } else {
+            None
+        };
+
         // Report the result
         if !matcher_main.quiet() {
             // Create a table
@@ -384,7 +613,7 @@ impl<'a> Upload<'a> {
             // Show the original URL when shortening, verbose and different
             #[cfg(feature = "urlshorten")]
             {
-                let full_url = file.download_url(true);
+                let full_url = file.download_url(secret.is_none());
                 if matcher_main.verbose() && matcher_upload.shorten() && url != full_url {
                     table.add_row(Row::new(vec![
                         Cell::new("Full share link:"),
@@ -397,39 +626,119 @@ impl<'a> Upload<'a> {
                 // Show the share URL
                 table.add_row(Row::new(vec![
                     Cell::new("Share link:"),
-                    Cell::new(url.as_str()),
+                    Cell::new(&format_url(&url)),
                 ]));
+                if let Some(secret) = &secret {
+                    table.add_row(Row::new(vec![Cell::new("Secret:"), Cell::new(secret)]));
+                }
 
-                // Show a generate passphrase
+                // Show a generate passphrase, or note a password is required
                 if password_generated {
                     table.add_row(Row::new(vec![
                         Cell::new("Passphrase:"),
-                        Cell::new(&password.unwrap_or("?".into())),
+                        Cell::new(&password.clone().unwrap_or("?".into())),
+                    ]));
+                } else if password.is_some() {
+                    table.add_row(Row::new(vec![
+                        Cell::new("Password:"),
+                        Cell::new("required"),
                     ]));
                 }
 
-                // Show the owner token
+                // Show the download limit
+                if let Some(limit) = download_limit {
+                    table.add_row(Row::new(vec![
+                        Cell::new("Download limit:"),
+                        Cell::new(&format!("{}", limit)),
+                    ]));
+                }
+
+                // Show the owner token, and the management link including it
                 table.add_row(Row::new(vec![
                     Cell::new("Owner token:"),
                     Cell::new(file.owner_token().unwrap()),
                 ]));
+                table.add_row(Row::new(vec![
+                    Cell::new("Management link:"),
+                    Cell::new(
+                        management_url(
+                            &file.download_url(secret.is_none()),
+                            file.owner_token().unwrap(),
+                        )
+                        .as_str(),
+                    ),
+                ]));
             } else {
                 table.add_row(Row::new(vec![Cell::new(url.as_str())]));
+                if let Some(secret) = &secret {
+                    table.add_row(Row::new(vec![Cell::new(secret)]));
+                }
 
                 // Show a generate passphrase
                 if password_generated {
-                    table.add_row(Row::new(vec![Cell::new(&password.unwrap_or("?".into()))]));
+                    table.add_row(Row::new(vec![Cell::new(
+                        &password.clone().unwrap_or("?".into()),
+                    )]));
                 }
             }
 
             table.printstd();
+
+            // Report a summary of the transfer
+            if let Some(size) = size {
+                let mut summary = format!(
+                    "Uploaded {}, expires in {}",
+                    format_transfer_summary(size, start.elapsed()),
+                    format_duration(file.expire_duration()),
+                );
+                if let Some(limit) = download_limit {
+                    summary.push_str(&format!(
+                        " or after {} download{}",
+                        limit,
+                        if limit == 1 { "" } else { "s" },
+                    ));
+                }
+                if password.is_some() {
+                    summary.push_str(", password required");
+                }
+                eprintln!("{}", summary);
+            }
+        } else if matcher_main.json() {
+            print_json(&UploadOutput {
+                id: file.id(),
+                url: url.as_str(),
+                secret: secret.as_ref().map(|s| s.as_str()),
+                owner: file.owner_token().map(|t| t.as_str()),
+                expiry: file.expire_duration().num_seconds(),
+                html: html.as_ref().map(|h| h.as_str()),
+            });
         } else {
             println!("{}", url);
+            if let Some(secret) = &secret {
+                println!("{}", secret);
+            }
         }
 
-        // Add the file to the history manager
-        #[cfg(feature = "history")]
-        history_tool::add(&matcher_main, file.clone(), false);
+        // Print an HTML snippet linking to the file, it's part of the output object in JSON mode
+        if !matcher_main.json() {
+            if let Some(html) = &html {
+                println!("{}", html);
+            }
+        }
+
+        // Notify the webhook of the uploaded file, don't fail the upload if it fails
+        if let Some(webhook) = matcher_upload.webhook() {
+            let webhook_client = create_webhook_config().client(false);
+            if let Err(err) = webhook::notify(
+                &webhook_client,
+                &webhook,
+                &file,
+                size,
+                matcher_upload.webhook_owner(),
+            ) {
+                print_error(err.context("failed to notify webhook, ignoring"));
+            }
+        }
 
         // Open the URL in the browser
         if matcher_upload.open() {
@@ -438,14 +747,20 @@ impl<'a> Upload<'a> {
             };
         }
 
-        // Copy the URL or command to the user's clipboard
+        // Copy the URL or command, and the owner token on it's own line, to the user's clipboard
         #[cfg(feature = "clipboard")]
         {
-            if let Some(copy_mode) = matcher_upload.copy() {
-                if let Err(err) = set_clipboard(copy_mode.build(url.as_str())) {
-                    print_error(
-                        err.context("failed to copy the share link to the clipboard, ignoring"),
-                    );
+            let mut copy: Vec<String> = matcher_upload
+                .copy()
+                .map(|copy_mode| copy_mode.build(url.as_str()))
+                .into_iter()
+                .collect();
+            if matcher_upload.copy_owner() {
+                copy.extend(file.owner_token().cloned());
+            }
+            if !copy.is_empty() {
+                if let Err(err) = set_clipboard(copy.join("\n")) {
+                    print_error(err.context("failed to copy to the clipboard, ignoring"));
                 }
             }
         }
@@ -473,10 +788,345 @@ impl<'a> Upload<'a> {
             }
         }
 
+        // Close the temporary stdin file, to ensure it's removed
+        if let Some(tmp_stdin) = tmp_stdin.take() {
+            if let Err(err) = tmp_stdin.close() {
+                print_error(
+                    err.context("failed to clean up temporary stdin file, ignoring")
+                        .compat(),
+                );
+            }
+        }
+
         Ok(())
     }
 }
 
+/// A snapshot of the state of a file, to detect it being changed while uploading.
+///
+/// The file is read by ffsend-api, so it can't be checked while each chunk is read. Instead the
+/// size and modification time are polled while uploading, and compared after uploading. A change
+/// is missed if the file is rewritten with the same size within the modification time resolution
+/// of the file system.
+#[derive(Clone, Debug, PartialEq)]
+struct FileState {
+    /// The file size in bytes.
+    size: u64,
+
+    /// The modification time, if supported by the platform.
+    modified: Option<SystemTime>,
+}
+
+impl FileState {
+    /// Get the current state of the file at the given path, `None` if it can't be read.
+    fn of(path: &Path) -> Option<Self> {
+        path.metadata().ok().map(|metadata| FileState {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// Watches a file while it's uploaded, to stop the upload when the file changes.
+///
+/// ffsend-api declares the upload length before reading the file, so a file shrinking while
+/// uploading makes the request wait for bytes that never come. The guard polls the file state,
+/// and raises the stall signal once it changed to stop waiting for the upload. It stops watching
+/// when dropped.
+#[derive(Clone)]
+struct FileGuard {
+    /// The file to watch.
+    path: PathBuf,
+
+    /// The state of the file before uploading.
+    state: Option<FileState>,
+
+    /// The signal to raise when the file changed.
+    signal: StallSignal,
+
+    /// Whether the file changed.
+    changed: Arc<AtomicBool>,
+
+    /// Whether to stop watching.
+    done: Arc<AtomicBool>,
+}
+
+impl FileGuard {
+    /// Start watching the file at `path` for changes from the given `state`.
+    ///
+    /// Nothing is watched if the file state is unknown.
+    fn watch(path: &Path, state: &Option<FileState>, signal: &StallSignal) -> Self {
+        let guard = FileGuard::new(path, state, signal);
+        if guard.state.is_some() {
+            let guard = guard.clone();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_millis(STALL_POLL_MILLIS));
+                if guard.check() {
+                    return;
+                }
+            });
+        }
+        guard
+    }
+
+    /// Construct a guard for the file at `path`, without watching it.
+    fn new(path: &Path, state: &Option<FileState>, signal: &StallSignal) -> Self {
+        FileGuard {
+            path: path.to_path_buf(),
+            state: state.clone(),
+            signal: signal.clone(),
+            changed: Arc::new(AtomicBool::new(false)),
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Check whether the file changed, and raise the signal if it did.
+    ///
+    /// True is returned once watching can stop, because the guard was dropped or the file changed.
+    fn check(&self) -> bool {
+        if self.done.load(Ordering::SeqCst) {
+            return true;
+        }
+        if FileState::of(&self.path) != self.state {
+            self.changed.store(true, Ordering::SeqCst);
+            self.signal.raise();
+            return true;
+        }
+        false
+    }
+
+    /// Whether the file was seen changing while uploading.
+    fn changed(&self) -> bool {
+        self.changed.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for FileGuard {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::SeqCst);
+    }
+}
+
+/// The upload result, printed in JSON output mode.
+#[derive(Serialize)]
+struct UploadOutput<'a> {
+    /// The file ID.
+    id: &'a str,
+
+    /// The share URL, including the secret unless it is shared separately.
+    url: &'a str,
+
+    /// The secret, only included when shared separately from the URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<&'a str>,
+
+    /// The owner token, used to manage the file.
+    owner: Option<&'a str>,
+
+    /// The number of seconds until the file expires.
+    expiry: i64,
+
+    /// An HTML snippet linking to the file, only included if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    html: Option<&'a str>,
+}
+
+/// Pick the name to share the file at `path` with, if no name is given, based on the `fallback`
+/// mode.
+///
+/// `None` is returned if the file has a usable name itself, this includes dotfiles and files
+/// without an extension. Otherwise the generic `file` name or the name of the parent directory is
+/// picked, an error is returned instead if a name is required.
+fn fallback_name(path: &Path, fallback: NameFallback) -> Result<Option<String>, Error> {
+    let usable = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map_or(false, |n| !n.trim().is_empty());
+    if usable {
+        return Ok(None);
+    }
+
+    match fallback {
+        NameFallback::File => Ok(Some("file".into())),
+        NameFallback::Parent => Ok(Some(
+            path.canonicalize()
+                .ok()
+                .as_ref()
+                .and_then(|p| p.parent())
+                .and_then(|p| p.file_name())
+                .and_then(|n| n.to_str())
+                .map(|n| n.to_owned())
+                .unwrap_or_else(|| "file".into()),
+        )),
+        NameFallback::Require => Err(Error::NoName),
+    }
+}
+
+/// Build a table explaining the upload request that would be made for the given file.
+///
+/// This is used for both `--explain` and `--dry-run`. Nothing is sent. If the server API version
+/// must be looked up, the version that is assumed is explained instead. Credentials are redacted.
+/// The size is left out when uploading from stdin, as it isn't known without reading it.
+fn explain(
+    matcher_main: &MainMatcher,
+    matcher_upload: &UploadMatcher,
+    host: &Url,
+    path: &Path,
+    file_name: Option<&String>,
+) -> Table {
+    let mut table = Table::new();
+    table.set_format(FormatBuilder::new().padding(0, 2).build());
+
+    // The host and API version
+    let (version, version_note) = match matcher_main.api() {
+        DesiredVersion::Use(version) => (version, ""),
+        DesiredVersion::Assume(version) => (version, " (assumed, looked up first)"),
+        DesiredVersion::Lookup => (API_VERSION_ASSUME, " (assumed, looked up first)"),
+    };
+    table.add_row(Row::new(vec![
+        Cell::new("Host:"),
+        Cell::new(&format_url(host)),
+    ]));
+    table.add_row(Row::new(vec![
+        Cell::new("API version:"),
+        Cell::new(&format!("v{}{}", version, version_note)),
+    ]));
+
+    // The endpoint and request layout, which depends on the API version
+    let (request, layout) = match version {
+        #[cfg(feature = "send2")]
+        ApiVersion::V2 => (
+            format!("POST {}", host.join("api/upload").unwrap()),
+            "multipart body with encrypted file as 'data', \
+             encrypted metadata in 'X-File-Metadata' header",
+        ),
+        #[cfg(feature = "send3")]
+        ApiVersion::V3 => (
+            format!("WebSocket {}", host.join("api/ws").unwrap()),
+            "JSON message with encrypted metadata and parameters, \
+             followed by encrypted file stream",
+        ),
+    };
+    table.add_row(Row::new(vec![Cell::new("Request:"), Cell::new(&request)]));
+    table.add_row(Row::new(vec![Cell::new("Layout:"), Cell::new(layout)]));
+    table.add_row(Row::new(vec![
+        Cell::new("Basic auth:"),
+        Cell::new(match matcher_main.basic_auth() {
+            Some(_) => "<redacted>",
+            None => "none",
+        }),
+    ]));
+
+    // The file and it's parameters
+    let name = file_name
+        .cloned()
+        .or_else(|| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.to_owned())
+        })
+        .unwrap_or_else(|| "file".into());
+    let file = if path == Path::new("-") {
+        "stdin".into()
+    } else {
+        path.to_string_lossy()
+    };
+    table.add_row(Row::new(vec![Cell::new("File:"), Cell::new(&file)]));
+    table.add_row(Row::new(vec![Cell::new("Name:"), Cell::new(&name)]));
+    if let Ok(size) = path.metadata().map(|m| m.len()) {
+        table.add_row(Row::new(vec![
+            Cell::new("Size:"),
+            Cell::new(&format!(
+                "{} ({} B, before encryption)",
+                format_bytes(size),
+                size
+            )),
+        ]));
+        let encrypted = encrypted_size(version, size);
+        table.add_row(Row::new(vec![
+            Cell::new("Encrypted size:"),
+            Cell::new(&format!(
+                "~{} ({} B, estimated)",
+                format_bytes(encrypted),
+                encrypted
+            )),
+        ]));
+    }
+    table.add_row(Row::new(vec![
+        Cell::new("Password:"),
+        Cell::new(if matcher_upload.has_password() {
+            "set"
+        } else {
+            "none"
+        }),
+    ]));
+    if let Some(limit) = matcher_upload.download_limit(matcher_main, version, false) {
+        table.add_row(Row::new(vec![
+            Cell::new("Download limit:"),
+            Cell::new(&format!("{}", limit)),
+        ]));
+    }
+    if let Some(part_size) = matcher_upload.split() {
+        table.add_row(Row::new(vec![
+            Cell::new("Split:"),
+            Cell::new(&format!("parts of {}", format_bytes(part_size))),
+        ]));
+    }
+
+    table
+}
+
+/// Estimate the size of a file of `size` bytes once encrypted for the given API version.
+///
+/// Send v2 appends a single AES-GCM tag. Send v3 uses an ECE stream: a header followed by
+/// fixed size records, each carrying a tag and a padding delimiter. Nothing is encrypted to
+/// determine this, so the actual size may differ if the server or encryption layout changes.
+fn encrypted_size(version: ApiVersion, size: u64) -> u64 {
+    const TAG_LEN: u64 = 16;
+
+    match version {
+        #[cfg(feature = "send2")]
+        ApiVersion::V2 => size + TAG_LEN,
+        #[cfg(feature = "send3")]
+        ApiVersion::V3 => {
+            const HEADER_LEN: u64 = 21;
+            const RECORD_SIZE: u64 = 64 * 1024;
+            let record_data = RECORD_SIZE - TAG_LEN - 1;
+            let records = ((size + record_data - 1) / record_data).max(1);
+            HEADER_LEN + size + records * (TAG_LEN + 1)
+        }
+    }
+}
+
+/// Build an HTML snippet linking to the shared file, for embedding in web pages.
+///
+/// A note on the download limit is included if set.
+fn html_snippet(url: &str, name: &str, download_limit: Option<u8>) -> String {
+    let mut html = format!(
+        "<a href=\"{}\" rel=\"noopener noreferrer\">Download {}</a>",
+        escape_html(url),
+        escape_html(name),
+    );
+    if let Some(limit) = download_limit {
+        html.push_str(&format!(
+            " <small>(limited to {} download{})</small>",
+            limit,
+            if limit == 1 { "" } else { "s" },
+        ));
+    }
+    html
+}
+
+/// Find the first of the given paths that is a symbolic link, if any.
+fn find_symlink<P: AsRef<Path>>(paths: &[P]) -> Option<&P> {
+    paths.iter().find(|path| {
+        path.as_ref()
+            .symlink_metadata()
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false)
+    })
+}
+
 /// Find the deepest directory all given paths share.
 ///
 /// This function canonicalizes the paths, make sure the paths exist.
@@ -557,9 +1207,29 @@ pub enum Error {
     #[fail(display = "failed to archive file to upload")]
     Archive(#[cause] ArchiveError),
 
+    /// Failed to read the file to upload from stdin.
+    #[fail(display = "failed to read file to upload from stdin")]
+    Stdin(#[cause] IoError),
+
     /// An error occurred while uploading the file.
     #[fail(display = "")]
     Upload(#[cause] UploadError),
+
+    /// An error occurred while uploading the file split into parts.
+    #[fail(display = "failed to upload split file")]
+    Split(#[cause] SplitError),
+
+    /// The upload stalled, or ended unexpectedly.
+    #[fail(display = "failed to upload the file")]
+    Watch(#[cause] WatchError),
+
+    /// The file was changed while it was being uploaded.
+    #[fail(display = "the file was changed while uploading, the upload was aborted")]
+    FileChanged,
+
+    /// No file name could be derived from the path, and a name is required.
+    #[fail(display = "failed to derive a file name from the path, specify one with --name")]
+    NoName,
 }
 
 impl From<VersionError> for Error {
@@ -581,6 +1251,18 @@ impl From<UploadError> for Error {
     }
 }
 
+impl From<SplitError> for Error {
+    fn from(err: SplitError) -> Error {
+        Error::Split(err)
+    }
+}
+
+impl From<WatchError> for Error {
+    fn from(err: WatchError) -> Error {
+        Error::Watch(err)
+    }
+}
+
 #[cfg(feature = "archive")]
 #[derive(Debug, Fail)]
 pub enum ArchiveError {
@@ -604,3 +1286,243 @@ pub enum ArchiveError {
     #[fail(display = "failed to write archive to disk")]
     Write(#[cause] IoError),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    #[test]
+    fn file_state_unchanged() {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), b"data").unwrap();
+        assert_eq!(FileState::of(file.path()), FileState::of(file.path()));
+    }
+
+    #[test]
+    fn file_state_size_changed() {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), b"data").unwrap();
+        let state = FileState::of(file.path());
+        fs::write(file.path(), b"more data").unwrap();
+        assert_ne!(FileState::of(file.path()), state);
+    }
+
+    #[test]
+    fn file_guard_unchanged() {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), b"data").unwrap();
+        let signal = StallSignal::default();
+        let guard = FileGuard::new(file.path(), &FileState::of(file.path()), &signal);
+        assert!(!guard.check());
+        assert!(!guard.changed());
+        assert!(!signal.is_raised());
+    }
+
+    #[test]
+    fn file_guard_shrunk() {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), b"data").unwrap();
+        let signal = StallSignal::default();
+        let guard = FileGuard::new(file.path(), &FileState::of(file.path()), &signal);
+        fs::write(file.path(), b"da").unwrap();
+        assert!(guard.check());
+        assert!(guard.changed());
+        assert!(signal.is_raised());
+    }
+
+    #[test]
+    fn file_guard_dropped() {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), b"data").unwrap();
+        let signal = StallSignal::default();
+        let guard = FileGuard::new(file.path(), &FileState::of(file.path()), &signal);
+        let watching = guard.clone();
+        drop(guard);
+        fs::write(file.path(), b"da").unwrap();
+        assert!(watching.check());
+        assert!(!watching.changed());
+        assert!(!signal.is_raised());
+    }
+
+    #[cfg(feature = "send2")]
+    #[test]
+    fn encrypted_size_v2() {
+        assert_eq!(encrypted_size(ApiVersion::V2, 0), 16);
+        assert_eq!(encrypted_size(ApiVersion::V2, 1000), 1016);
+    }
+
+    #[cfg(feature = "send3")]
+    #[test]
+    fn encrypted_size_v3() {
+        // A header, and a single record for empty and small files
+        assert_eq!(encrypted_size(ApiVersion::V3, 0), 21 + 17);
+        assert_eq!(encrypted_size(ApiVersion::V3, 1000), 21 + 1000 + 17);
+
+        // A new record is started once a record is filled
+        let record_data = 64 * 1024 - 17;
+        assert_eq!(
+            encrypted_size(ApiVersion::V3, record_data),
+            21 + record_data + 17
+        );
+        assert_eq!(
+            encrypted_size(ApiVersion::V3, record_data + 1),
+            21 + record_data + 1 + 2 * 17
+        );
+    }
+
+    #[test]
+    fn fallback_name_usable() {
+        for name in &[
+            "report.pdf",
+            "Makefile",
+            ".bashrc",
+            ".config.toml",
+            "dir/archive.tar.gz",
+        ] {
+            for fallback in &[
+                NameFallback::File,
+                NameFallback::Parent,
+                NameFallback::Require,
+            ] {
+                assert_eq!(
+                    fallback_name(Path::new(name), *fallback).unwrap(),
+                    None,
+                    "{}",
+                    name,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fallback_name_file() {
+        assert_eq!(
+            fallback_name(Path::new("/"), NameFallback::File).unwrap(),
+            Some("file".into()),
+        );
+        assert_eq!(
+            fallback_name(Path::new("dir/.."), NameFallback::File).unwrap(),
+            Some("file".into()),
+        );
+    }
+
+    #[test]
+    fn fallback_name_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+
+        // The canonical path is `<dir>/a`, it's parent is the temporary directory
+        let expected = dir
+            .path()
+            .canonicalize()
+            .unwrap()
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| n.to_owned());
+        assert_eq!(
+            fallback_name(&dir.path().join("a/b/.."), NameFallback::Parent).unwrap(),
+            expected,
+        );
+
+        // Fall back to the generic name if there's no parent either
+        assert_eq!(
+            fallback_name(Path::new("/"), NameFallback::Parent).unwrap(),
+            Some("file".into()),
+        );
+    }
+
+    #[test]
+    fn fallback_name_require() {
+        match fallback_name(Path::new("/"), NameFallback::Require) {
+            Err(Error::NoName) => {}
+            _ => panic!("expected a missing name error"),
+        }
+    }
+
+    #[test]
+    fn file_state_missing() {
+        assert_eq!(FileState::of(Path::new("/nonexistent/ffsend-file")), None);
+    }
+
+    #[test]
+    fn upload_output_json() {
+        let output = UploadOutput {
+            id: "abc",
+            url: "https://send.example.com/download/abc/#secret",
+            secret: None,
+            owner: Some("token"),
+            expiry: 86400,
+            html: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&output).unwrap(),
+            r#"{"id":"abc","url":"https://send.example.com/download/abc/#secret","owner":"token","expiry":86400}"#,
+        );
+    }
+
+    #[test]
+    fn upload_output_json_optional() {
+        let output = UploadOutput {
+            id: "abc",
+            url: "https://send.example.com/download/abc/",
+            secret: Some("secret"),
+            owner: None,
+            expiry: 60,
+            html: Some("<a></a>"),
+        };
+        let json: serde_json::Value = serde_json::to_value(&output).unwrap();
+        assert_eq!(json["secret"], "secret");
+        assert!(json["owner"].is_null());
+        assert_eq!(json["html"], "<a></a>");
+    }
+
+    #[test]
+    fn html_snippet_link() {
+        assert_eq!(
+            html_snippet(
+                "https://send.example.com/download/abc/#secret",
+                "report.pdf",
+                None
+            ),
+            "<a href=\"https://send.example.com/download/abc/#secret\" \
+             rel=\"noopener noreferrer\">Download report.pdf</a>",
+        );
+    }
+
+    #[test]
+    fn html_snippet_escapes() {
+        let html = html_snippet("https://example.com/?a=1&b=\"2\"", "<b>x</b>.txt", None);
+        assert!(html.contains("href=\"https://example.com/?a=1&amp;b=&quot;2&quot;\""));
+        assert!(html.contains("Download &lt;b&gt;x&lt;/b&gt;.txt</a>"));
+    }
+
+    #[test]
+    fn html_snippet_download_limit() {
+        assert!(html_snippet("https://example.com/", "a", Some(1))
+            .ends_with("</a> <small>(limited to 1 download)</small>"));
+        assert!(html_snippet("https://example.com/", "a", Some(5))
+            .ends_with("</a> <small>(limited to 5 downloads)</small>"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_symlink_file_and_dir() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file.txt");
+        let sub = dir.path().join("sub");
+        fs::write(&file, "file").unwrap();
+        fs::create_dir(&sub).unwrap();
+        let (link_file, link_sub) = (dir.path().join("link-file"), dir.path().join("link-sub"));
+        symlink(&file, &link_file).unwrap();
+        symlink(&sub, &link_sub).unwrap();
+
+        // Regular paths are fine, a link to either a file or a directory is refused
+        assert_eq!(find_symlink(&[&file, &sub]), None);
+        assert_eq!(find_symlink(&[&file, &link_file]), Some(&&link_file));
+        assert_eq!(find_symlink(&[&link_sub, &file]), Some(&&link_sub));
+    }
+}
@@ -5,6 +5,15 @@ use crate::client::create_config;
 use crate::cmd::matcher::main::MainMatcher;
 use crate::cmd::matcher::{version::VersionMatcher, Matcher};
 use crate::error::ActionError;
+use crate::util::print_warning;
+
+/// The server API versions this client is compiled with support for.
+const SUPPORTED_VERSIONS: &[&str] = &[
+    #[cfg(feature = "send2")]
+    "v2",
+    #[cfg(feature = "send3")]
+    "v3",
+];
 
 /// A file version action.
 pub struct Version<'a> {
@@ -38,7 +47,13 @@ impl<'a> Version<'a> {
         match response {
             Ok(v) => println!("API version: {}", v),
             Err(VersionError::Unknown) => println!("Version: unknown"),
-            Err(VersionError::Unsupported(v)) => println!("Version: {} (unsupported)", v),
+            Err(VersionError::Unsupported(v)) => {
+                println!("Version: {} (unsupported)", v);
+                print_warning(format!(
+                    "this server uses an API version not supported by this client, supported: {}",
+                    SUPPORTED_VERSIONS.join(", "),
+                ));
+            }
             Err(e) => return Err(e.into()),
         }
 
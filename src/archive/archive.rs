@@ -1,5 +1,8 @@
-use std::io::{Error as IoError, Read};
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::{self, Error as IoError, ErrorKind, Read};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
 
 use super::tar::Archive as TarArchive;
 
@@ -18,8 +21,245 @@ impl<R: Read> Archive<R> {
         }
     }
 
-    /// Extract the archive to the given destination.
-    pub fn extract<P: AsRef<Path>>(&mut self, destination: P) -> Result<()> {
-        self.inner.unpack(destination)
+    /// Extract the archive to the given destination, enforcing the given limits.
+    ///
+    /// Extraction is aborted if the bytes written for extracted entries would exceed `max_size`,
+    /// or if the archive holds more than `max_entries` entries. The declared size of each entry is
+    /// checked before it is extracted, the bytes actually written are counted while extracting.
+    /// When aborting, everything extracted so far is removed, including created directories.
+    pub fn extract<P: AsRef<Path>>(
+        &mut self,
+        destination: P,
+        max_size: u64,
+        max_entries: usize,
+    ) -> Result<()> {
+        let destination = destination.as_ref();
+        let mut extracted: Vec<PathBuf> = Vec::new();
+
+        let result = self.extract_entries(destination, max_size, max_entries, &mut extracted);
+
+        // Clean up everything extracted so far on failure, contents before their directories
+        if result.is_err() {
+            for path in extracted.iter().rev() {
+                let is_dir = path
+                    .symlink_metadata()
+                    .map(|m| m.file_type().is_dir())
+                    .unwrap_or(false);
+                let _ = if is_dir {
+                    fs::remove_dir(path)
+                } else {
+                    fs::remove_file(path)
+                };
+            }
+        }
+
+        result
+    }
+
+    /// Extract all entries to the given destination within the given limits, collecting the
+    /// created files and directories in `extracted` in the order they were created.
+    fn extract_entries(
+        &mut self,
+        destination: &Path,
+        max_size: u64,
+        max_entries: usize,
+        extracted: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        let root = destination.canonicalize()?;
+        let mut written: u64 = 0;
+
+        for (i, entry) in self.inner.entries()?.enumerate() {
+            let mut entry = entry?;
+
+            // Enforce the limits before extracting the entry
+            if i >= max_entries {
+                return Err(IoError::new(
+                    ErrorKind::Other,
+                    format!("archive has more than {} entries", max_entries),
+                ));
+            }
+            if written.saturating_add(entry.header().size()?) > max_size {
+                return Err(too_large(max_size));
+            }
+
+            // Determine where to extract the entry, create it's parent directories
+            let path = match entry_path(&root, &entry.path()?)? {
+                Some(path) => path,
+                None => continue,
+            };
+            if let Some(parent) = path.parent() {
+                create_dirs(&root, parent, extracted)?;
+            }
+
+            let kind = entry.header().entry_type();
+            if kind.is_dir() {
+                create_dirs(&root, &path, extracted)?;
+            } else if kind.is_file() || kind.is_gnu_sparse() {
+                // Never write through a symbolic link extracted earlier
+                let is_symlink = path
+                    .symlink_metadata()
+                    .map(|m| m.file_type().is_symlink())
+                    .unwrap_or(false);
+                if is_symlink {
+                    return Err(IoError::new(
+                        ErrorKind::Other,
+                        format!("archive entry overwrites a link: {}", path.display()),
+                    ));
+                }
+
+                // Write the contents, stop as soon as the limit is exceeded
+                let mut file = File::create(&path)?;
+                extracted.push(path.clone());
+                let remaining = max_size - written;
+                written += io::copy(
+                    &mut (&mut entry).take(remaining.saturating_add(1)),
+                    &mut file,
+                )?;
+                if written > max_size {
+                    return Err(too_large(max_size));
+                }
+
+                #[cfg(unix)]
+                {
+                    let mode = entry.header().mode()? & 0o777;
+                    file.set_permissions(fs::Permissions::from_mode(mode))?;
+                }
+            } else if entry.unpack_in(&root)? {
+                extracted.push(path);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Build the error for archive contents exceeding `max_size` bytes.
+fn too_large(max_size: u64) -> IoError {
+    IoError::new(
+        ErrorKind::Other,
+        format!("archive contents exceed {} bytes", max_size),
+    )
+}
+
+/// Resolve the path of an archive entry within the `root` directory to extract to.
+///
+/// Absolute paths are made relative to the root, paths escaping it through `..` are refused.
+/// `None` is returned for entries resolving to the root itself.
+fn entry_path(root: &Path, path: &Path) -> Result<Option<PathBuf>> {
+    let mut target = root.to_path_buf();
+    let mut nested = false;
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => {
+                target.push(part);
+                nested = true;
+            }
+            Component::ParentDir => {
+                return Err(IoError::new(
+                    ErrorKind::Other,
+                    format!("archive entry escapes the destination: {}", path.display()),
+                ))
+            }
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+    Ok(if nested { Some(target) } else { None })
+}
+
+/// Create the directory `dir` and it's missing parents within `root`, collecting the created
+/// directories in `created`, parents first.
+///
+/// Directories resolving outside the root, through a symbolic link, are refused.
+fn create_dirs(root: &Path, dir: &Path, created: &mut Vec<PathBuf>) -> Result<()> {
+    let missing: Vec<&Path> = dir
+        .ancestors()
+        .take_while(|p| *p != root && !p.exists())
+        .collect();
+    for dir in missing.into_iter().rev() {
+        fs::create_dir(dir)?;
+        created.push(dir.to_path_buf());
+    }
+
+    if !dir.canonicalize()?.starts_with(root) {
+        return Err(IoError::new(
+            ErrorKind::Other,
+            format!("archive entry escapes the destination: {}", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tar::{Builder, Header};
+    use super::*;
+
+    use tempfile::tempdir;
+
+    /// Build a tar archive holding files with the given paths and sizes.
+    fn archive(files: &[(&str, usize)]) -> Vec<u8> {
+        let mut builder = Builder::new(Vec::new());
+        for (path, size) in files {
+            let mut header = Header::new_gnu();
+            header.set_size(*size as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, &vec![b'x'; *size][..])
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn extract_within_limits() {
+        let dir = tempdir().unwrap();
+        let data = archive(&[("a.txt", 10), ("sub/dir/b.txt", 20)]);
+        Archive::new(&data[..]).extract(dir.path(), 30, 10).unwrap();
+        assert_eq!(fs::read(dir.path().join("a.txt")).unwrap().len(), 10);
+        assert_eq!(
+            fs::read(dir.path().join("sub/dir/b.txt")).unwrap().len(),
+            20
+        );
+    }
+
+    #[test]
+    fn extract_too_large_cleans_up() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("existing"), b"keep").unwrap();
+        let data = archive(&[("a.txt", 10), ("sub/dir/b.txt", 20), ("c.txt", 10)]);
+        assert!(Archive::new(&data[..]).extract(dir.path(), 35, 10).is_err());
+
+        // Created files and directories are removed, existing files are kept
+        let mut entries: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, vec!["existing"]);
+    }
+
+    #[test]
+    fn extract_too_many_entries() {
+        let dir = tempdir().unwrap();
+        let data = archive(&[("a.txt", 1), ("b.txt", 1)]);
+        assert!(Archive::new(&data[..]).extract(dir.path(), 10, 1).is_err());
+        assert!(!dir.path().join("a.txt").exists());
+    }
+
+    #[test]
+    fn entry_path_traversal() {
+        let root = Path::new("/dest");
+        assert_eq!(
+            entry_path(root, Path::new("a/b.txt")).unwrap(),
+            Some(root.join("a/b.txt")),
+        );
+        assert_eq!(
+            entry_path(root, Path::new("/etc/passwd")).unwrap(),
+            Some(root.join("etc/passwd")),
+        );
+        assert_eq!(entry_path(root, Path::new("./")).unwrap(), None);
+        assert!(entry_path(root, Path::new("../escape")).is_err());
+        assert!(entry_path(root, Path::new("a/../../escape")).is_err());
     }
 }
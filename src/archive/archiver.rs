@@ -1,6 +1,8 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{Error as IoError, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
 
 use super::tar::Builder as TarBuilder;
 
@@ -9,6 +11,12 @@ pub type Result<T> = ::std::result::Result<T, IoError>;
 pub struct Archiver<W: Write> {
     /// The tar builder.
     inner: TarBuilder<W>,
+
+    /// Whether to follow symbolic links inside appended directories.
+    follow_symlinks: bool,
+
+    /// Patterns for entries to exclude when appending directories.
+    exclude: Vec<Pattern>,
 }
 
 impl<W: Write> Archiver<W> {
@@ -16,9 +24,27 @@ impl<W: Write> Archiver<W> {
     pub fn new(writer: W) -> Archiver<W> {
         Archiver {
             inner: TarBuilder::new(writer),
+            follow_symlinks: true,
+            exclude: Vec::new(),
         }
     }
 
+    /// Set whether to follow symbolic links when appending directories.
+    ///
+    /// If `true`, the targets of symbolic links are added to the archive. If `false`, the links
+    /// themselves are stored instead. Symbolic links are followed by default.
+    pub fn follow_symlinks(&mut self, follow: bool) {
+        self.follow_symlinks = follow;
+        self.inner.follow_symlinks(follow);
+    }
+
+    /// Set patterns for entries to exclude when appending directories.
+    ///
+    /// A pattern is matched against both the entry name and it's relative path in the archive.
+    pub fn exclude(&mut self, exclude: Vec<Pattern>) {
+        self.exclude = exclude;
+    }
+
     /// Add the entry at the given `src` path, to the given relative `path` in the archive.
     ///
     /// If a directory path is given, the whole directory including it's contents is added to the
@@ -49,14 +75,62 @@ impl<W: Write> Archiver<W> {
         self.inner.append_file(path, file)
     }
 
-    /// Append a directory to the archive builder.
+    /// Append a directory to the archive builder, including all it's contents.
+    ///
+    /// Empty directories are preserved, excluded entries are skipped. When following symbolic
+    /// links, a link to a directory that is already being appended is skipped to break cycles.
     // TODO: Define a flag to add recursively or not
     pub fn append_dir<P, Q>(&mut self, path: P, src_path: Q) -> Result<()>
     where
         P: AsRef<Path>,
         Q: AsRef<Path>,
     {
-        self.inner.append_dir_all(path, src_path)
+        self.append_dir_recursive(path.as_ref(), src_path.as_ref(), &mut Vec::new())
+    }
+
+    /// Append a directory and it's contents, `ancestors` being the directories appended above it.
+    fn append_dir_recursive(
+        &mut self,
+        path: &Path,
+        src_path: &Path,
+        ancestors: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        // Skip directories we're already in, to prevent looping on symbolic link cycles
+        let canonical = src_path.canonicalize()?;
+        if ancestors.contains(&canonical) {
+            return Ok(());
+        }
+        ancestors.push(canonical);
+
+        // Add the directory itself, then each of it's entries in a stable order
+        self.inner.append_dir(path, src_path)?;
+        let mut entries = fs::read_dir(src_path)?.collect::<Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let entry_path = path.join(entry.file_name());
+            if self.excluded(&entry_path) {
+                continue;
+            }
+
+            let entry_src = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() || (self.follow_symlinks && entry_src.is_dir()) {
+                self.append_dir_recursive(&entry_path, &entry_src, ancestors)?;
+            } else {
+                self.inner.append_path_with_name(&entry_src, &entry_path)?;
+            }
+        }
+
+        ancestors.pop();
+        Ok(())
+    }
+
+    /// Check whether the entry at the given relative `path` in the archive is excluded.
+    fn excluded(&self, path: &Path) -> bool {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        self.exclude
+            .iter()
+            .any(|pattern| pattern.matches(name) || pattern.matches_path(path))
     }
 
     // TODO: some description
@@ -64,3 +138,137 @@ impl<W: Write> Archiver<W> {
         self.inner.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::tar::{Archive as TarArchive, EntryType};
+
+    /// Archive the given directory as `dir` with the given exclude patterns and symbolic link
+    /// behavior, and list the path and type of each archived entry.
+    fn archive(src: &Path, exclude: &[&str], follow: bool) -> Vec<(String, EntryType)> {
+        let mut archiver = Archiver::new(Vec::new());
+        archiver.follow_symlinks(follow);
+        archiver.exclude(exclude.iter().map(|p| Pattern::new(p).unwrap()).collect());
+        archiver.append_dir("dir", src).unwrap();
+        let data = archiver.inner.into_inner().unwrap();
+
+        TarArchive::new(data.as_slice())
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let path = entry
+                    .path()
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .trim_end_matches('/')
+                    .to_owned();
+                (path, entry.header().entry_type())
+            })
+            .collect()
+    }
+
+    /// List the paths of the given archived entries.
+    fn paths(entries: &[(String, EntryType)]) -> Vec<&str> {
+        entries.iter().map(|(path, _)| path.as_str()).collect()
+    }
+
+    #[test]
+    fn exclude_name() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        fs::write(dir.path().join("a.log"), "a").unwrap();
+        fs::write(dir.path().join("sub/b.log"), "b").unwrap();
+
+        let entries = archive(dir.path(), &["*.log"], false);
+        assert_eq!(paths(&entries), vec!["dir", "dir/a.txt", "dir/sub"]);
+    }
+
+    #[test]
+    fn exclude_relative_path() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("keep")).unwrap();
+        fs::create_dir(dir.path().join("skip")).unwrap();
+        fs::write(dir.path().join("keep/a.txt"), "a").unwrap();
+        fs::write(dir.path().join("skip/a.txt"), "a").unwrap();
+
+        let entries = archive(dir.path(), &["dir/skip/*"], false);
+        assert_eq!(
+            paths(&entries),
+            vec!["dir", "dir/keep", "dir/keep/a.txt", "dir/skip"],
+        );
+    }
+
+    #[test]
+    fn empty_dir_preserved() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("empty")).unwrap();
+
+        let entries = archive(dir.path(), &[], false);
+        assert_eq!(paths(&entries), vec!["dir", "dir/empty"]);
+        assert_eq!(entries[1].1, EntryType::Directory);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycle_followed() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/a.txt"), "a").unwrap();
+        symlink(dir.path(), dir.path().join("sub/loop")).unwrap();
+
+        // The link back to the archived directory is skipped, rather than looping forever
+        let entries = archive(dir.path(), &[], true);
+        assert_eq!(paths(&entries), vec!["dir", "dir/sub", "dir/sub/a.txt"]);
+    }
+
+    #[test]
+    fn follow_symlinks_default() {
+        assert!(Archiver::new(Vec::new()).follow_symlinks);
+    }
+
+    /// Create a directory holding a file, a directory, and symbolic links to both.
+    #[cfg(unix)]
+    fn symlinks() -> tempfile::TempDir {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/a.txt"), "a").unwrap();
+        fs::write(dir.path().join("file.txt"), "file").unwrap();
+        symlink(dir.path().join("file.txt"), dir.path().join("link-file")).unwrap();
+        symlink(dir.path().join("sub"), dir.path().join("link-sub")).unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_stored() {
+        let dir = symlinks();
+        let entries = archive(dir.path(), &[], false);
+        let links: Vec<_> = entries
+            .iter()
+            .filter(|(_, kind)| *kind == EntryType::Symlink)
+            .map(|(path, _)| path.as_str())
+            .collect();
+        assert_eq!(links, vec!["dir/link-file", "dir/link-sub"]);
+        assert!(!paths(&entries).contains(&"dir/link-sub/a.txt"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_dereferenced() {
+        let dir = symlinks();
+        let entries = archive(dir.path(), &[], true);
+        assert!(entries.iter().all(|(_, kind)| *kind != EntryType::Symlink));
+        assert!(entries.contains(&("dir/link-file".into(), EntryType::Regular)));
+        assert!(entries.contains(&("dir/link-sub".into(), EntryType::Directory)));
+        assert!(entries.contains(&("dir/link-sub/a.txt".into(), EntryType::Regular)));
+    }
+}
@@ -3,6 +3,7 @@ use std::time::Duration;
 use ffsend_api::client::{ClientConfig, ClientConfigBuilder};
 
 use crate::cmd::matcher::MainMatcher;
+use crate::config::WEBHOOK_TIMEOUT;
 
 /// Create a client configuration for ffsend actions.
 ///
@@ -10,11 +11,35 @@ use crate::cmd::matcher::MainMatcher;
 /// actions.
 // TODO: properly handle errors, do not unwrap
 pub fn create_config(matcher_main: &MainMatcher) -> ClientConfig {
+    // Log the configuration, never log the credentials themselves
+    let basic_auth = matcher_main.basic_auth();
+    debug!(
+        "client timeout: {}s, transfer timeout: {}s",
+        matcher_main.timeout(),
+        matcher_main.transfer_timeout(),
+    );
+    if basic_auth.is_some() {
+        debug!("using basic authentication, sending the Authorization header");
+    }
+
     // TODO: configure HTTP authentication properties
     ClientConfigBuilder::default()
         .timeout(to_duration(matcher_main.timeout()))
         .transfer_timeout(to_duration(matcher_main.transfer_timeout()))
-        .basic_auth(matcher_main.basic_auth())
+        .basic_auth(basic_auth)
+        .build()
+        .expect("failed to create network client configuration")
+}
+
+/// Create a client configuration for webhook notifications.
+///
+/// This uses a short timeout, and doesn't use the basic authentication credentials meant for the
+/// Send host.
+pub fn create_webhook_config() -> ClientConfig {
+    ClientConfigBuilder::default()
+        .timeout(to_duration(WEBHOOK_TIMEOUT))
+        .transfer_timeout(to_duration(WEBHOOK_TIMEOUT))
+        .basic_auth(None)
         .build()
         .expect("failed to create network client configuration")
 }
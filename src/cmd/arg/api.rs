@@ -3,6 +3,7 @@ use ffsend_api::api::{DesiredVersion, Version};
 
 use super::{CmdArg, CmdArgOption};
 use crate::config::API_VERSION_DESIRED_DEFAULT;
+use crate::config_file;
 use crate::util::{quit_error_msg, ErrorHints};
 
 /// The api argument.
@@ -36,18 +37,18 @@ impl<'a> CmdArgOption<'a> for ArgApi {
 
     fn value<'b: 'a>(matches: &'a ArgMatches<'b>) -> Self::Value {
         // Get the version string
-        let version = match Self::value_raw(matches) {
+        let version = match config_file::value(matches, Self::name()) {
             Some(version) => version,
             None => return API_VERSION_DESIRED_DEFAULT,
         };
 
         // Parse the lookup version string
-        if is_auto(version) {
+        if is_auto(&version) {
             return DesiredVersion::Lookup;
         }
 
         // Parse the given API version
-        match Version::parse(version) {
+        match Version::parse(&version) {
             Ok(version) => DesiredVersion::Use(version),
             Err(_) => quit_error_msg(
                 "failed to determine given server API version, version unknown",
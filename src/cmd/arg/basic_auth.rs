@@ -1,6 +1,7 @@
 use clap::{Arg, ArgMatches};
 
 use super::{CmdArg, CmdArgOption};
+use crate::config_file;
 
 /// The basicauth argument.
 pub struct ArgBasicAuth {}
@@ -29,7 +30,7 @@ impl<'a> CmdArgOption<'a> for ArgBasicAuth {
 
     fn value<'b: 'a>(matches: &'a ArgMatches<'b>) -> Self::Value {
         // Get the authentication credentials
-        let raw = match Self::value_raw(matches) {
+        let raw = match config_file::value(matches, Self::name()) {
             Some(raw) => raw,
             None => return None,
         };
@@ -0,0 +1,20 @@
+use clap::Arg;
+
+use super::{CmdArg, CmdArgFlag};
+
+/// The owner token check argument.
+pub struct ArgCheckOwner {}
+
+impl CmdArg for ArgCheckOwner {
+    fn name() -> &'static str {
+        "check-owner"
+    }
+
+    fn build<'b, 'c>() -> Arg<'b, 'c> {
+        Arg::with_name("check-owner")
+            .long("check-owner")
+            .help("Verify the owner token before modifying the file")
+    }
+}
+
+impl CmdArgFlag for ArgCheckOwner {}
@@ -69,6 +69,12 @@ impl CmdArg for ArgDownloadLimit {
             .alias("download")
             .value_name("COUNT")
             .help("The file download limit")
+            .validator(|arg| match arg.parse::<usize>() {
+                Ok(limit) if limit > 0 => Ok(()),
+                _ => Err(String::from(
+                    "Download limit must be a positive number of downloads",
+                )),
+            })
     }
 }
 
@@ -78,7 +84,6 @@ impl<'a> CmdArgOption<'a> for ArgDownloadLimit {
     type Value = Option<usize>;
 
     fn value<'b: 'a>(matches: &'a ArgMatches<'b>) -> Self::Value {
-        // TODO: do not unwrap, report an error
         Self::value_raw(matches).map(|d| d.parse::<usize>().expect("invalid download limit"))
     }
 }
@@ -4,12 +4,38 @@ use ffsend_api::config::SEND_DEFAULT_HOST;
 use ffsend_api::url::Url;
 
 use super::{CmdArg, CmdArgOption};
-use crate::host::parse_host;
-use crate::util::{quit_error, ErrorHints};
+use crate::cmd::matcher::{MainMatcher, Matcher};
+use crate::config_file;
+use crate::host::{check_https, normalize_host, warn_plaintext};
+use crate::util::{highlight, quit_error, ErrorHints, ErrorHintsBuilder};
 
 /// The host argument.
 pub struct ArgHost {}
 
+impl ArgHost {
+    /// Ensure the given host or share URL doesn't use plaintext HTTP, unless allowed.
+    ///
+    /// A warning is printed if plaintext HTTP is allowed and used. The program will quit with an
+    /// error message if it isn't allowed.
+    pub fn ensure_https(url: &Url, matches: &ArgMatches) {
+        let matcher_main = MainMatcher::with(matches).unwrap();
+        if let Err(err) = check_https(url, matcher_main.allow_http()) {
+            quit_error(
+                err,
+                ErrorHintsBuilder::default()
+                    .add_info(format!(
+                        "Use '{}' to use it anyway",
+                        highlight("--allow-http")
+                    ))
+                    .verbose(false)
+                    .build()
+                    .unwrap(),
+            );
+        }
+        warn_plaintext(url);
+    }
+}
+
 impl CmdArg for ArgHost {
     fn name() -> &'static str {
         "host"
@@ -32,15 +58,18 @@ impl<'a> CmdArgOption<'a> for ArgHost {
 
     fn value<'b: 'a>(matches: &'a ArgMatches<'b>) -> Self::Value {
         // Get the URL
-        let url = Self::value_raw(matches).expect("missing host");
+        let url = config_file::value(matches, Self::name()).expect("missing host");
 
-        // Parse the URL
-        match parse_host(&url) {
+        // Parse and normalize the URL
+        let url = match normalize_host(&url) {
             Ok(url) => url,
             Err(err) => quit_error(
                 err.context("failed to parse the given host"),
                 ErrorHints::default(),
             ),
-        }
+        };
+
+        Self::ensure_https(&url, matches);
+        url
     }
 }
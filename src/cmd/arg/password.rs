@@ -2,7 +2,8 @@ use clap::{Arg, ArgMatches};
 
 use super::{CmdArg, CmdArgFlag, CmdArgOption};
 use crate::cmd::matcher::{MainMatcher, Matcher};
-use crate::util::{check_empty_password, prompt_password};
+use crate::config_file;
+use crate::util::{check_empty_password, prompt_new_password, prompt_password};
 
 /// The password argument.
 pub struct ArgPassword {}
@@ -19,28 +20,48 @@ impl CmdArg for ArgPassword {
             .value_name("PASSWORD")
             .min_values(0)
             .max_values(1)
+            .env("FFSEND_PASSWORD")
+            .hide_env_values(true)
             .help("Unlock a password protected file")
     }
 }
 
+impl ArgPassword {
+    /// Get a new password to set.
+    ///
+    /// Like `value`, but the user is asked to confirm the password when prompted for it.
+    pub fn value_new<'a>(matches: &ArgMatches<'a>, prompt: &str) -> Option<String> {
+        // Create a main matcher
+        let matcher_main = MainMatcher::with(matches).unwrap();
+
+        // Get the password argument value, prompt if the flag is given without one
+        let password = match config_file::value(matches, Self::name()) {
+            Some(password) => password.into(),
+            None if Self::is_present(matches) => prompt_new_password(&matcher_main, prompt),
+            None => return None,
+        };
+
+        // Check for empty passwords
+        check_empty_password(&password, &matcher_main);
+
+        Some(password)
+    }
+}
+
 impl CmdArgFlag for ArgPassword {}
 
 impl<'a> CmdArgOption<'a> for ArgPassword {
     type Value = Option<String>;
 
     fn value<'b: 'a>(matches: &'a ArgMatches<'b>) -> Self::Value {
-        // The password flag must be present
-        if !Self::is_present(matches) {
-            return None;
-        }
-
         // Create a main matcher
         let matcher_main = MainMatcher::with(matches).unwrap();
 
-        // Get the password argument value, or prompt
-        let password = match Self::value_raw(matches) {
+        // Get the password argument value, prompt if the flag is given without one
+        let password = match config_file::value(matches, Self::name()) {
             Some(password) => password.into(),
-            None => prompt_password(&matcher_main, false).unwrap(),
+            None if Self::is_present(matches) => prompt_password(&matcher_main, false).unwrap(),
+            None => return None,
         };
 
         // Check for empty passwords
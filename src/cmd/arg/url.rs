@@ -1,14 +1,31 @@
 use clap::{Arg, ArgMatches};
-use failure::Fail;
 use ffsend_api::url::Url;
 
-use super::{CmdArg, CmdArgOption};
-use crate::host::parse_host;
+use super::{ArgHost, CmdArg, CmdArgOption};
+use crate::host::{parse_share_url, take_owner_token};
 use crate::util::{quit_error, ErrorHints};
 
 /// The URL argument.
 pub struct ArgUrl {}
 
+impl ArgUrl {
+    /// Get the owner token, if the given URL is a management URL that includes it.
+    pub fn owner(matches: &ArgMatches) -> Option<String> {
+        take_owner_token(&mut Self::parse(matches))
+    }
+
+    /// Parse the given share URL or share code.
+    ///
+    /// If the given URL is invalid, the program will quit with an error message.
+    fn parse(matches: &ArgMatches) -> Url {
+        let url = matches.value_of(Self::name()).expect("missing URL");
+        match parse_share_url(url) {
+            Ok(url) => url,
+            Err(err) => quit_error(err, ErrorHints::default()),
+        }
+    }
+}
+
 impl CmdArg for ArgUrl {
     fn name() -> &'static str {
         "URL"
@@ -18,7 +35,7 @@ impl CmdArg for ArgUrl {
         Arg::with_name("URL")
             .required(true)
             .multiple(false)
-            .help("The share URL")
+            .help("The share URL or 'host#id:secret' share code")
     }
 }
 
@@ -26,16 +43,10 @@ impl<'a> CmdArgOption<'a> for ArgUrl {
     type Value = Url;
 
     fn value<'b: 'a>(matches: &'a ArgMatches<'b>) -> Self::Value {
-        // Get the URL
-        let url = Self::value_raw(matches).expect("missing URL");
-
-        // Parse the URL
-        match parse_host(&url) {
-            Ok(url) => url,
-            Err(err) => quit_error(
-                err.context("failed to parse the given share URL"),
-                ErrorHints::default(),
-            ),
-        }
+        // Parse the URL, strip the owner token from management URLs
+        let mut url = Self::parse(matches);
+        take_owner_token(&mut url);
+        ArgHost::ensure_https(&url, matches);
+        url
     }
 }
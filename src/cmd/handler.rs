@@ -2,6 +2,7 @@ extern crate directories;
 
 #[cfg(feature = "infer-command")]
 use std::ffi::OsString;
+use std::path::PathBuf;
 
 use clap::{App, AppSettings, Arg, ArgMatches};
 
@@ -21,10 +22,12 @@ use super::subcmd::{
 #[cfg(feature = "infer-command")]
 use crate::config::INFER_COMMANDS;
 use crate::config::{CLIENT_TIMEOUT, CLIENT_TRANSFER_TIMEOUT};
+use crate::config_file::Settings;
 #[cfg(feature = "history")]
 use crate::util::app_history_file_path_string;
 #[cfg(feature = "infer-command")]
 use crate::util::bin_name;
+use crate::util::{app_config_file_path, quit_error, quit_error_msg, ErrorHints};
 
 #[cfg(feature = "history")]
 lazy_static! {
@@ -133,6 +136,38 @@ impl<'a: 'b, 'b> Handler<'a> {
                         ))
                     ),
             )
+            .arg(
+                Arg::with_name("retries")
+                    .long("retries")
+                    .value_name("COUNT")
+                    .global(true)
+                    .help("Retry failed transfers this many times")
+                    .env("FFSEND_RETRIES")
+                    .hide_env_values(true)
+                    .validator(|arg| arg
+                        .parse::<u32>()
+                        .map(|_| ())
+                        .map_err(|_| String::from(
+                                "Retries must be a positive number of retries, or 0 to disable."
+                        ))
+                    ),
+            )
+            .arg(
+                Arg::with_name("stall-timeout")
+                    .long("stall-timeout")
+                    .value_name("SECONDS")
+                    .global(true)
+                    .help("Abort transfers making no progress for this long (0 to disable)")
+                    .env("FFSEND_STALL_TIMEOUT")
+                    .hide_env_values(true)
+                    .validator(|arg| arg
+                        .parse::<u64>()
+                        .map(|_| ())
+                        .map_err(|_| String::from(
+                                "Stall timeout must be a positive number of seconds, or 0 to disable."
+                        ))
+                    ),
+            )
             .arg(
                 Arg::with_name("quiet")
                     .long("quiet")
@@ -146,7 +181,85 @@ impl<'a: 'b, 'b> Handler<'a> {
                     .short("v")
                     .multiple(true)
                     .global(true)
-                    .help("Enable verbose information and logging"),
+                    .help("Enable verbose information and logging, repeat for more"),
+            )
+            .arg(
+                Arg::with_name("si")
+                    .long("si")
+                    .global(true)
+                    .help("Show sizes in powers of 1000 rather than 1024"),
+            )
+            .arg(
+                Arg::with_name("allow-http")
+                    .long("allow-http")
+                    .global(true)
+                    .help("Allow plaintext http:// hosts, exposing the file secret"),
+            )
+            .arg(
+                Arg::with_name("json")
+                    .long("json")
+                    .global(true)
+                    .conflicts_with("verbose")
+                    .help("Produce machine-readable JSON output"),
+            )
+            .arg(
+                Arg::with_name("config")
+                    .long("config")
+                    .value_name("FILE")
+                    .global(true)
+                    .help("Use defaults from the specified configuration file")
+                    .env("FFSEND_CONFIG")
+                    .hide_env_values(true),
+            )
+            .arg(
+                Arg::with_name("profile")
+                    .long("profile")
+                    .value_name("NAME")
+                    .global(true)
+                    .help("Use the named profile from the configuration file")
+                    .env("FFSEND_PROFILE")
+                    .hide_env_values(true),
+            )
+            .arg(
+                Arg::with_name("no-progress")
+                    .long("no-progress")
+                    .global(true)
+                    .help("Don't show transfer progress"),
+            )
+            .arg(
+                Arg::with_name("progress-socket")
+                    .long("progress-socket")
+                    .value_name("PATH")
+                    .global(true)
+                    .help("Report transfer progress to a socket or named pipe")
+                    .env("FFSEND_PROGRESS_SOCKET")
+                    .hide_env_values(true),
+            )
+            .arg(
+                Arg::with_name("metrics-file")
+                    .long("metrics-file")
+                    .value_name("FILE")
+                    .global(true)
+                    .help("Write transfer metrics to a file after running, '-' for stdout")
+                    .long_help(
+                        "Write transfer metrics to a file after running, '-' for stdout.\n\n\
+                         Metrics are also written when quitting with an error. Writing them to \
+                         stdout can't be combined with '--json', as it would mix with the JSON \
+                         output.",
+                    )
+                    .env("FFSEND_METRICS_FILE")
+                    .hide_env_values(true),
+            )
+            .arg(
+                Arg::with_name("metrics-format")
+                    .long("metrics-format")
+                    .value_name("FORMAT")
+                    .global(true)
+                    .possible_values(&["json", "prometheus"])
+                    .default_value("json")
+                    .help("The format to write transfer metrics in")
+                    .env("FFSEND_METRICS_FORMAT")
+                    .hide_env_values(true),
             )
             .arg(ArgApi::build())
             .arg(ArgBasicAuth::build())
@@ -206,9 +319,28 @@ impl<'a: 'b, 'b> Handler<'a> {
         Self::infer_subcommand(&mut args);
 
         // Build the application CLI definition, get the matches
-        Handler {
-            matches: Handler::build().get_matches_from(args),
+        let matches = Handler::build().get_matches_from(args);
+
+        // Use the given configuration file, or the default one if it exists
+        let config = matches
+            .value_of("config")
+            .map(PathBuf::from)
+            .or_else(|| app_config_file_path().filter(|path| path.is_file()));
+
+        // Load the configuration file if any, use it's values as defaults
+        match (config, matches.value_of("profile")) {
+            (Some(path), profile) => match Settings::load(&path, profile) {
+                Ok(settings) => settings.apply(),
+                Err(err) => quit_error(err, ErrorHints::default()),
+            },
+            (None, Some(_)) => quit_error_msg(
+                "a profile can only be selected along with a configuration file",
+                ErrorHints::default(),
+            ),
+            (None, None) => {}
         }
+
+        Handler { matches }
     }
 
     /// Infer subcommand when the binary has a predefined name,
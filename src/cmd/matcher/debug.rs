@@ -18,6 +18,11 @@ impl<'a: 'b, 'b> DebugMatcher<'a> {
     pub fn host(&'a self) -> Url {
         ArgHost::value(self.matches)
     }
+
+    /// Check whether to diagnose the connection to the host.
+    pub fn diagnose(&self) -> bool {
+        self.matches.is_present("diagnose")
+    }
 }
 
 impl<'a> Matcher<'a> for DebugMatcher<'a> {
@@ -2,7 +2,7 @@ use clap::ArgMatches;
 use ffsend_api::url::Url;
 
 use super::Matcher;
-use crate::cmd::arg::{ArgOwner, ArgUrl, CmdArgOption};
+use crate::cmd::arg::{ArgCheckOwner, ArgOwner, ArgUrl, CmdArgFlag, CmdArgOption};
 
 /// The delete command matcher.
 pub struct DeleteMatcher<'a> {
@@ -20,9 +20,18 @@ impl<'a: 'b, 'b> DeleteMatcher<'a> {
     }
 
     /// Get the owner token.
+    ///
+    /// The token embedded in a management URL is used if not specified.
     pub fn owner(&'a self) -> Option<String> {
         // TODO: just return a string reference here?
-        ArgOwner::value(self.matches).map(|token| token.to_owned())
+        ArgOwner::value(self.matches)
+            .map(|token| token.to_owned())
+            .or_else(|| ArgUrl::owner(self.matches))
+    }
+
+    /// Check whether to verify the owner token before modifying the file.
+    pub fn check_owner(&'a self) -> bool {
+        ArgCheckOwner::is_present(self.matches)
     }
 }
 
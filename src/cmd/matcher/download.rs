@@ -6,7 +6,11 @@ use ffsend_api::url::Url;
 use super::Matcher;
 use crate::cmd::arg::{ArgPassword, ArgUrl, CmdArgOption};
 #[cfg(feature = "archive")]
+use crate::config::{EXTRACT_ENTRIES_MAX, EXTRACT_SIZE_MAX};
+use crate::config_file;
 use crate::util::env_var_present;
+#[cfg(feature = "archive")]
+use crate::util::parse_size;
 
 /// The download command matcher.
 pub struct DownloadMatcher<'a> {
@@ -40,9 +44,20 @@ impl<'a: 'b, 'b> DownloadMatcher<'a> {
         ArgPassword::value(self.matches)
     }
 
+    /// Get the file secret to use if the share URL doesn't include it.
+    pub fn secret(&'a self) -> Option<&'a str> {
+        self.matches.value_of("secret")
+    }
+
+    /// Get the file to read share URLs from, to download all of them.
+    pub fn from_file(&'a self) -> Option<PathBuf> {
+        self.matches.value_of("from-file").map(PathBuf::from)
+    }
+
     /// The target file or directory to download the file to.
     /// If a directory is given, the file name of the original uploaded file
     /// will be used.
+    /// The path may contain placeholders for file metadata, which are expanded when downloading.
     pub fn output(&'a self) -> PathBuf {
         self.matches
             .value_of("output")
@@ -50,10 +65,61 @@ impl<'a: 'b, 'b> DownloadMatcher<'a> {
             .unwrap_or_else(|| PathBuf::from("./"))
     }
 
+    /// Get the shell command to pipe the downloaded file into, if set.
+    pub fn pipe(&self) -> Option<&'a str> {
+        self.matches.value_of("pipe")
+    }
+
+    /// Check whether to write the downloaded file to stdout.
+    pub fn stdout(&self) -> bool {
+        self.matches.is_present("stdout")
+    }
+
+    /// Check whether to pick a new file name if the output file already exists, rather than
+    /// overwriting it.
+    pub fn rename_on_conflict(&self) -> bool {
+        self.matches.is_present("rename-on-conflict")
+            || env_var_present("FFSEND_RENAME_ON_CONFLICT")
+            || config_file::flag("rename-on-conflict")
+    }
+
+    /// Check whether to detect the type of the downloaded file from it's contents.
+    pub fn detect_type(&self) -> bool {
+        self.matches.is_present("detect-type")
+    }
+
     /// Check whether to extract an archived file.
     #[cfg(feature = "archive")]
     pub fn extract(&self) -> bool {
-        self.matches.is_present("extract") || env_var_present("FFSEND_EXTRACT")
+        self.matches.is_present("extract")
+            || env_var_present("FFSEND_EXTRACT")
+            || config_file::flag("extract")
+    }
+
+    /// Get the maximum total size in bytes of extracted archive contents.
+    #[cfg(feature = "archive")]
+    pub fn max_extract_size(&self) -> u64 {
+        self.matches
+            .value_of("max-extract-size")
+            .map(|size| parse_size(size).expect("invalid extract size"))
+            .unwrap_or(EXTRACT_SIZE_MAX)
+    }
+
+    /// Get the maximum number of entries to extract from an archive.
+    #[cfg(feature = "archive")]
+    pub fn max_extract_entries(&self) -> usize {
+        self.matches
+            .value_of("max-extract-entries")
+            .map(|count| count.parse().expect("invalid extract entry count"))
+            .unwrap_or(EXTRACT_ENTRIES_MAX)
+    }
+
+    /// Check whether to copy the contents of the downloaded file to the clipboard.
+    #[cfg(feature = "clipboard")]
+    pub fn clip(&self) -> bool {
+        self.matches.is_present("clip")
+            || env_var_present("FFSEND_CLIP")
+            || config_file::flag("clip")
     }
 }
 
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::ArgMatches;
 use failure::Fail;
 use ffsend_api::url::Url;
@@ -18,6 +20,36 @@ impl<'a> HistoryMatcher<'a> {
         self.matches.is_present("clear")
     }
 
+    /// Check whether to export the history.
+    pub fn export(&self) -> bool {
+        self.matches.is_present("export")
+    }
+
+    /// Get the exported history file to import, if set.
+    pub fn import(&self) -> Option<PathBuf> {
+        self.matches.value_of("import").map(PathBuf::from)
+    }
+
+    /// Check whether to replace the current history when importing.
+    pub fn replace(&self) -> bool {
+        self.matches.is_present("replace")
+    }
+
+    /// Check whether to check the integrity of history entries.
+    pub fn check(&self) -> bool {
+        self.matches.is_present("check")
+    }
+
+    /// Check whether to probe the server for the existence of each file when checking.
+    pub fn probe(&self) -> bool {
+        self.matches.is_present("probe")
+    }
+
+    /// Check whether to remove corrupt or dead entries when checking.
+    pub fn fix(&self) -> bool {
+        self.matches.is_present("fix")
+    }
+
     /// Check whether to remove a given entry from the history.
     ///
     /// This method parses the URL into an `Url`.
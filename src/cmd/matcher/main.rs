@@ -1,4 +1,3 @@
-#[cfg(feature = "history")]
 use std::path::PathBuf;
 
 use clap::ArgMatches;
@@ -6,6 +5,8 @@ use ffsend_api::api::DesiredVersion;
 
 use super::Matcher;
 use crate::cmd::arg::{ArgApi, ArgBasicAuth, CmdArgOption};
+use crate::config_file;
+use crate::metrics::Format as MetricsFormat;
 use crate::util::env_var_present;
 #[cfg(feature = "history")]
 use crate::util::{quit_error_msg, ErrorHintsBuilder};
@@ -18,17 +19,21 @@ pub struct MainMatcher<'a> {
 impl<'a: 'b, 'b> MainMatcher<'a> {
     /// Check whether to force.
     pub fn force(&self) -> bool {
-        self.matches.is_present("force") || env_var_present("FFSEND_FORCE")
+        self.matches.is_present("force")
+            || env_var_present("FFSEND_FORCE")
+            || config_file::flag("force")
     }
 
     /// Check whether to use no-interact mode.
     pub fn no_interact(&self) -> bool {
-        self.matches.is_present("no-interact") || env_var_present("FFSEND_NO_INTERACT")
+        self.matches.is_present("no-interact")
+            || env_var_present("FFSEND_NO_INTERACT")
+            || config_file::flag("no-interact")
     }
 
     /// Check whether to assume yes.
     pub fn assume_yes(&self) -> bool {
-        self.matches.is_present("yes") || env_var_present("FFSEND_YES")
+        self.matches.is_present("yes") || env_var_present("FFSEND_YES") || config_file::flag("yes")
     }
 
     /// Get the desired API version to use.
@@ -45,7 +50,7 @@ impl<'a: 'b, 'b> MainMatcher<'a> {
     #[cfg(feature = "history")]
     pub fn history(&self) -> PathBuf {
         // Get the path
-        let path = self.matches.value_of("history").map(PathBuf::from);
+        let path = config_file::value(self.matches, "history").map(|path| PathBuf::from(&*path));
 
         // Ensure the path is correct
         match path {
@@ -63,34 +68,109 @@ impl<'a: 'b, 'b> MainMatcher<'a> {
 
     /// Get the timeout in seconds
     pub fn timeout(&self) -> u64 {
-        self.matches
-            .value_of("timeout")
+        config_file::value(self.matches, "timeout")
             .and_then(|arg| arg.parse().ok())
             .expect("invalid timeout value")
     }
 
     /// Get the transfer timeout in seconds
     pub fn transfer_timeout(&self) -> u64 {
-        self.matches
-            .value_of("transfer-timeout")
+        config_file::value(self.matches, "transfer-timeout")
             .and_then(|arg| arg.parse().ok())
             .expect("invalid transfer-timeout value")
     }
 
+    /// Get the number of times to retry failed transfers.
+    pub fn retries(&self) -> u32 {
+        config_file::value(self.matches, "retries")
+            .map(|arg| arg.parse().expect("invalid retries value"))
+            .unwrap_or(0)
+    }
+
+    /// Get the stall timeout in seconds, if set.
+    ///
+    /// `None` is returned if stall detection is disabled.
+    pub fn stall_timeout(&self) -> Option<u64> {
+        config_file::value(self.matches, "stall-timeout")
+            .map(|arg| arg.parse().expect("invalid stall-timeout value"))
+            .filter(|timeout| *timeout > 0)
+    }
+
+    /// Check whether not to show transfer progress.
+    pub fn no_progress(&self) -> bool {
+        self.matches.is_present("no-progress")
+            || env_var_present("FFSEND_NO_PROGRESS")
+            || config_file::flag("no-progress")
+    }
+
+    /// Get the socket or named pipe to report transfer progress to, if set.
+    pub fn progress_socket(&self) -> Option<PathBuf> {
+        config_file::value(self.matches, "progress-socket").map(|path| PathBuf::from(&*path))
+    }
+
+    /// Get the file to write transfer metrics to, if set.
+    pub fn metrics_file(&self) -> Option<PathBuf> {
+        config_file::value(self.matches, "metrics-file").map(|path| PathBuf::from(&*path))
+    }
+
+    /// Get the format to write transfer metrics in.
+    pub fn metrics_format(&self) -> MetricsFormat {
+        config_file::value(self.matches, "metrics-format")
+            .and_then(|format| MetricsFormat::parse(&format))
+            .unwrap_or(MetricsFormat::Json)
+    }
+
+    /// Check whether to format sizes in SI units, being powers of 1000.
+    pub fn si(&self) -> bool {
+        self.matches.is_present("si") || env_var_present("FFSEND_SI") || config_file::flag("si")
+    }
+
     /// Check whether we are incognito from the file history.
     #[cfg(feature = "history")]
     pub fn incognito(&self) -> bool {
-        self.matches.is_present("incognito") || env_var_present("FFSEND_INCOGNITO")
+        self.matches.is_present("incognito")
+            || env_var_present("FFSEND_INCOGNITO")
+            || config_file::flag("incognito")
     }
 
     /// Check whether quiet mode is used.
+    ///
+    /// Quiet mode is always used when producing JSON output.
     pub fn quiet(&self) -> bool {
-        !self.verbose() && (self.matches.is_present("quiet") || env_var_present("FFSEND_QUIET"))
+        self.json()
+            || (!self.verbose()
+                && (self.matches.is_present("quiet")
+                    || env_var_present("FFSEND_QUIET")
+                    || config_file::flag("quiet")))
+    }
+
+    /// Check whether to allow plaintext `http://` hosts.
+    pub fn allow_http(&self) -> bool {
+        self.matches.is_present("allow-http")
+            || env_var_present("FFSEND_ALLOW_HTTP")
+            || config_file::flag("allow-http")
+    }
+
+    /// Check whether to produce machine-readable JSON output.
+    pub fn json(&self) -> bool {
+        self.matches.is_present("json")
+            || env_var_present("FFSEND_JSON")
+            || config_file::flag("json")
     }
 
     /// Check whether verbose mode is used.
     pub fn verbose(&self) -> bool {
-        self.matches.is_present("verbose") || env_var_present("FFSEND_VERBOSE")
+        self.matches.is_present("verbose")
+            || env_var_present("FFSEND_VERBOSE")
+            || config_file::flag("verbose")
+    }
+
+    /// Get the verbosity level, being the number of times verbose mode is enabled.
+    pub fn verbosity(&self) -> u64 {
+        match self.matches.occurrences_of("verbose") {
+            0 if self.verbose() => 1,
+            count => count,
+        }
     }
 }
 
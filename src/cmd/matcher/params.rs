@@ -24,9 +24,13 @@ impl<'a: 'b, 'b> ParamsMatcher<'a> {
     }
 
     /// Get the owner token.
+    ///
+    /// The token embedded in a management URL is used if not specified.
     pub fn owner(&'a self) -> Option<String> {
         // TODO: just return a string reference here?
-        ArgOwner::value(self.matches).map(|token| token.to_owned())
+        ArgOwner::value(self.matches)
+            .map(|token| token.to_owned())
+            .or_else(|| ArgUrl::owner(self.matches))
     }
 
     /// Get the download limit.
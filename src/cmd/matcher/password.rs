@@ -1,10 +1,11 @@
 use clap::ArgMatches;
 use ffsend_api::url::Url;
-use rpassword::prompt_password_stderr;
 
-use crate::cmd::arg::{ArgGenPassphrase, ArgOwner, ArgPassword, ArgUrl, CmdArgFlag, CmdArgOption};
+use crate::cmd::arg::{
+    ArgCheckOwner, ArgGenPassphrase, ArgOwner, ArgPassword, ArgUrl, CmdArgFlag, CmdArgOption,
+};
 use crate::cmd::matcher::{MainMatcher, Matcher};
-use crate::util::check_empty_password;
+use crate::util::{check_empty_password, prompt_new_password};
 
 /// The password command matcher.
 pub struct PasswordMatcher<'a> {
@@ -22,9 +23,18 @@ impl<'a: 'b, 'b> PasswordMatcher<'a> {
     }
 
     /// Get the owner token.
+    ///
+    /// The token embedded in a management URL is used if not specified.
     pub fn owner(&'a self) -> Option<String> {
         // TODO: just return a string reference here?
-        ArgOwner::value(self.matches).map(|token| token.to_owned())
+        ArgOwner::value(self.matches)
+            .map(|token| token.to_owned())
+            .or_else(|| ArgUrl::owner(self.matches))
+    }
+
+    /// Check whether to verify the owner token before modifying the file.
+    pub fn check_owner(&'a self) -> bool {
+        ArgCheckOwner::is_present(self.matches)
     }
 
     /// Get the password.
@@ -37,21 +47,13 @@ impl<'a: 'b, 'b> PasswordMatcher<'a> {
             return (ArgGenPassphrase::gen_passphrase(), true);
         }
 
-        // Get the password, or prompt for it
-        let password = match ArgPassword::value(self.matches) {
-            Some(password) => password,
-            None => {
-                // Prompt for the password
-                // TODO: don't unwrap/expect
-                // TODO: create utility function for this
-                prompt_password_stderr("New password: ")
-                    .expect("failed to read password from stdin")
-            }
-        };
-
         // Create a main matcher
         let matcher_main = MainMatcher::with(self.matches).unwrap();
 
+        // Get the password, or prompt for it
+        let password = ArgPassword::value_new(self.matches, "New password: ")
+            .unwrap_or_else(|| prompt_new_password(&matcher_main, "New password: "));
+
         // Check for empty passwords
         check_empty_password(&password, &matcher_main);
 
@@ -2,13 +2,17 @@ use clap::ArgMatches;
 use ffsend_api::action::params::PARAMS_DEFAULT_DOWNLOAD as DOWNLOAD_DEFAULT;
 use ffsend_api::api::Version as ApiVersion;
 use ffsend_api::url::Url;
+#[cfg(feature = "archive")]
+use glob::Pattern;
 
 use super::Matcher;
 use crate::cmd::{
     arg::{ArgDownloadLimit, ArgGenPassphrase, ArgHost, ArgPassword, CmdArgFlag, CmdArgOption},
     matcher::MainMatcher,
 };
-use crate::util::{bin_name, env_var_present, quit_error_msg, ErrorHintsBuilder};
+use crate::config_file;
+use crate::host::parse_host;
+use crate::util::{bin_name, env_var_present, parse_size, quit_error_msg, ErrorHintsBuilder};
 
 /// The upload command matcher.
 pub struct UploadMatcher<'a> {
@@ -27,7 +31,8 @@ impl<'a: 'b, 'b> UploadMatcher<'a> {
 
     /// The the name to use for the uploaded file.
     /// If no custom name is given, none is returned.
-    // TODO: validate custom names, no path separators
+    ///
+    /// The name is used as is, it must be sanitized before use.
     // TODO: only allow extension renaming with force flag
     pub fn name(&'a self) -> Option<&'a str> {
         // Get the chosen file name
@@ -49,6 +54,15 @@ impl<'a: 'b, 'b> UploadMatcher<'a> {
         Some(name)
     }
 
+    /// Get what to name the file if no name is given, and it can't be derived from the path.
+    pub fn name_fallback(&self) -> NameFallback {
+        match self.matches.value_of("name-fallback") {
+            Some("parent") => NameFallback::Parent,
+            Some("require") => NameFallback::Require,
+            _ => NameFallback::File,
+        }
+    }
+
     /// Get the host to upload to.
     ///
     /// This method parses the host into an `Url`.
@@ -72,7 +86,17 @@ impl<'a: 'b, 'b> UploadMatcher<'a> {
         }
 
         // Use a specified password or use nothing
-        ArgPassword::value(self.matches).map(|password| (password, false))
+        ArgPassword::value_new(self.matches, "Password: ").map(|password| (password, false))
+    }
+
+    /// Check whether a password is set or generated, without prompting for it.
+    pub fn has_password(&self) -> bool {
+        ArgGenPassphrase::is_present(self.matches) || ArgPassword::is_present(self.matches)
+    }
+
+    /// Check whether to explain the upload request instead of uploading.
+    pub fn explain(&self) -> bool {
+        self.matches.is_present("explain")
     }
 
     /// Get the download limit.
@@ -93,23 +117,105 @@ impl<'a: 'b, 'b> UploadMatcher<'a> {
         )
     }
 
+    /// Get the size of parts to split the file into, in bytes.
+    ///
+    /// If the file should not be split, `None` is returned.
+    pub fn split(&self) -> Option<u64> {
+        self.matches
+            .value_of("split")
+            .map(|size| parse_size(size).expect("invalid split size"))
+    }
+
+    /// Get the maximum file size the server allows, in bytes.
+    ///
+    /// If not set, `None` is returned and the default for the API version should be used.
+    pub fn max_size(&self) -> Option<u64> {
+        self.matches
+            .value_of("max-size")
+            .map(|size| parse_size(size).expect("invalid maximum size"))
+    }
+
     /// Check whether to archive the file to upload.
     #[cfg(feature = "archive")]
     pub fn archive(&self) -> bool {
-        self.matches.is_present("archive") || env_var_present("FFSEND_ARCHIVE")
+        self.matches.is_present("archive")
+            || env_var_present("FFSEND_ARCHIVE")
+            || config_file::flag("archive")
+    }
+
+    /// Check whether to follow symbolic links inside archived directories.
+    ///
+    /// If not set, symbolic links are stored in the archive as is.
+    #[cfg(feature = "archive")]
+    pub fn dereference(&self) -> bool {
+        self.matches.is_present("dereference")
+    }
+
+    /// Get the patterns for files to exclude from archived directories.
+    #[cfg(feature = "archive")]
+    pub fn exclude(&self) -> Vec<Pattern> {
+        self.matches
+            .values_of("exclude")
+            .map(|patterns| {
+                patterns
+                    .map(|p| Pattern::new(p).expect("invalid exclude pattern"))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Check whether not to follow a symbolic link given as file to upload.
+    ///
+    /// Top-level symbolic links are followed by default.
+    pub fn no_follow(&self) -> bool {
+        self.matches.is_present("no-follow")
     }
 
     /// Check whether to open the file URL in the user's browser.
     pub fn open(&self) -> bool {
-        self.matches.is_present("open") || env_var_present("FFSEND_OPEN")
+        self.matches.is_present("open")
+            || env_var_present("FFSEND_OPEN")
+            || config_file::flag("open")
+    }
+
+    /// Check whether to show the share link without it's secret, and the secret separately.
+    pub fn no_secret_url(&self) -> bool {
+        self.matches.is_present("no-secret-url")
+    }
+
+    /// Get the webhook to post the uploaded file details to, if set.
+    ///
+    /// Like the host, the webhook may only use plaintext HTTP if allowed, as the posted details
+    /// include the share link and it's secret.
+    pub fn webhook(&self) -> Option<Url> {
+        let url = self
+            .matches
+            .value_of("webhook")
+            .map(|url| parse_host(url).expect("invalid webhook URL"))?;
+        ArgHost::ensure_https(&url, self.matches);
+        Some(url)
+    }
+
+    /// Check whether to include the owner token in the webhook details.
+    pub fn webhook_owner(&self) -> bool {
+        self.matches.is_present("webhook-owner")
+    }
+
+    /// Check whether to print an HTML snippet linking to the file.
+    pub fn html(&self) -> bool {
+        self.matches.is_present("html")
     }
 
     /// Check whether to copy the file URL in the user's clipboard, get the copy mode.
     #[cfg(feature = "clipboard")]
     pub fn copy(&self) -> Option<CopyMode> {
         // Get the options
-        let copy = self.matches.is_present("copy") || env_var_present("FFSEND_COPY");
-        let copy_cmd = self.matches.is_present("copy-cmd") || env_var_present("FFSEND_COPY_CMD");
+        let copy = self.matches.is_present("copy")
+            || env_var_present("FFSEND_COPY")
+            || config_file::flag("copy");
+        let copy_cmd = self.matches.is_present("copy-cmd")
+            || env_var_present("FFSEND_COPY_CMD")
+            || config_file::flag("copy-cmd");
 
         // Return the corresponding copy mode
         if copy_cmd {
@@ -121,6 +227,16 @@ impl<'a: 'b, 'b> UploadMatcher<'a> {
         }
     }
 
+    /// Check whether to copy the owner token to the user's clipboard.
+    ///
+    /// This is independent of the copy mode, both may be copied at once.
+    #[cfg(feature = "clipboard")]
+    pub fn copy_owner(&self) -> bool {
+        self.matches.is_present("copy-owner")
+            || env_var_present("FFSEND_COPY_OWNER")
+            || config_file::flag("copy-owner")
+    }
+
     /// Check whether to shorten a share URL
     #[cfg(feature = "urlshorten")]
     pub fn shorten(&self) -> bool {
@@ -134,6 +250,19 @@ impl<'a: 'b, 'b> UploadMatcher<'a> {
     }
 }
 
+/// What to name an uploaded file, if no name is given and it can't be derived from the path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameFallback {
+    /// Use the generic `file` name.
+    File,
+
+    /// Use the name of the parent directory.
+    Parent,
+
+    /// Require a name to be given, fail otherwise.
+    Require,
+}
+
 impl<'a> Matcher<'a> for UploadMatcher<'a> {
     fn with(matches: &'a ArgMatches) -> Option<Self> {
         matches
@@ -153,7 +282,7 @@ pub enum CopyMode {
 }
 
 impl CopyMode {
-    /// Build the string to copy, based on the given `url` and currend mode.
+    /// Build the string to copy, based on the given `url` and current mode.
     pub fn build(&self, url: &str) -> String {
         match self {
             CopyMode::Url => url.into(),
@@ -1,4 +1,4 @@
-use clap::{App, SubCommand};
+use clap::{App, Arg, SubCommand};
 
 use crate::cmd::arg::{ArgHost, CmdArg};
 
@@ -11,5 +11,10 @@ impl CmdDebug {
             .about("View debug information")
             .visible_alias("dbg")
             .arg(ArgHost::build().hidden(true))
+            .arg(
+                Arg::with_name("diagnose")
+                    .long("diagnose")
+                    .help("Diagnose the connection to the host"),
+            )
     }
 }
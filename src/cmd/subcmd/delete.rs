@@ -1,6 +1,6 @@
 use clap::{App, SubCommand};
 
-use crate::cmd::arg::{ArgOwner, ArgUrl, CmdArg};
+use crate::cmd::arg::{ArgCheckOwner, ArgOwner, ArgUrl, CmdArg};
 
 /// The delete command definition.
 pub struct CmdDelete;
@@ -13,5 +13,6 @@ impl CmdDelete {
             .visible_alias("rm")
             .arg(ArgUrl::build())
             .arg(ArgOwner::build())
+            .arg(ArgCheckOwner::build())
     }
 }
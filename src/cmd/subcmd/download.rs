@@ -1,6 +1,8 @@
 use clap::{App, Arg, SubCommand};
 
 use crate::cmd::arg::{ArgPassword, ArgUrl, CmdArg};
+#[cfg(feature = "archive")]
+use crate::util::parse_size;
 
 /// The download command definition.
 pub struct CmdDownload;
@@ -13,7 +15,7 @@ impl CmdDownload {
             .about("Download files")
             .visible_alias("d")
             .visible_alias("down")
-            .arg(ArgUrl::build())
+            .arg(ArgUrl::build().required_unless("from-file"))
             .arg(ArgPassword::build())
             .arg(
                 Arg::with_name("output")
@@ -23,20 +25,111 @@ impl CmdDownload {
                     .alias("out")
                     .alias("file")
                     .value_name("PATH")
-                    .help("Output file or directory"),
+                    .help("Output file or directory, may use {name}, {mime} or {size}"),
+            )
+            .arg(
+                Arg::with_name("stdout")
+                    .long("stdout")
+                    .conflicts_with_all(&["output", "pipe", "rename-on-conflict", "detect-type"])
+                    .help("Write the downloaded file to stdout")
+                    .long_help(
+                        "Write the downloaded file to stdout.\n\n\
+                         The file is decrypted and verified as a whole before it is written, so \
+                         it is first written to a temporary file, only accessible by you. The \
+                         temporary file is deleted afterwards, also when the download fails.",
+                    ),
+            )
+            .arg(
+                Arg::with_name("secret")
+                    .long("secret")
+                    .short("s")
+                    .value_name("SECRET")
+                    .help("The file secret, if not included in the share URL"),
+            )
+            .arg(
+                Arg::with_name("from-file")
+                    .long("from-file")
+                    .value_name("FILE")
+                    .conflicts_with("URL")
+                    .help("Download all share URLs listed in a file, one per line"),
+            )
+            .arg(
+                Arg::with_name("pipe")
+                    .long("pipe")
+                    .value_name("COMMAND")
+                    .conflicts_with_all(&["output", "rename-on-conflict", "detect-type"])
+                    .help("Pipe the downloaded file into the standard input of a command")
+                    .long_help(
+                        "Pipe the downloaded file into the standard input of a command.\n\n\
+                         The file is streamed into the command while it is downloaded, so it \
+                         can't be verified as a whole first. If the download fails, the command \
+                         is killed, though it may have processed part of the file already.",
+                    ),
+            )
+            .arg(
+                Arg::with_name("detect-type")
+                    .long("detect-type")
+                    .help("Detect the type of the downloaded file, warn if it mismatches"),
+            )
+            .arg(
+                Arg::with_name("rename-on-conflict")
+                    .long("rename-on-conflict")
+                    .alias("rename")
+                    .help("Save as 'name (1).ext' if the output file already exists"),
             );
 
         // Optional archive support
         #[cfg(feature = "archive")]
+        {
+            cmd = cmd
+                .arg(
+                    Arg::with_name("extract")
+                        .long("extract")
+                        .short("e")
+                        .alias("archive")
+                        .alias("arch")
+                        .alias("a")
+                        .help("Extract an archived file"),
+                )
+                .arg(
+                    Arg::with_name("max-extract-size")
+                        .long("max-extract-size")
+                        .value_name("SIZE")
+                        .help("Abort extracting if the contents exceed this size")
+                        .long_help(
+                            "Abort extracting if the contents exceed this size, 16 GiB by \
+                             default.\n\n\
+                             The bytes actually written are counted. When aborting, everything \
+                             extracted so far is removed.",
+                        )
+                        .validator(|arg| match parse_size(&arg) {
+                            Some(size) if size > 0 => Ok(()),
+                            _ => Err(String::from(
+                                "Extract size must be a positive size, such as '500M' or '1G'",
+                            )),
+                        }),
+                )
+                .arg(
+                    Arg::with_name("max-extract-entries")
+                        .long("max-extract-entries")
+                        .value_name("COUNT")
+                        .help("Abort extracting if the archive has more entries")
+                        .validator(|arg| match arg.parse::<usize>() {
+                            Ok(count) if count > 0 => Ok(()),
+                            _ => Err(String::from("Entry count must be a positive number")),
+                        }),
+                )
+        }
+
+        // Optional clipboard support
+        #[cfg(feature = "clipboard")]
         {
             cmd = cmd.arg(
-                Arg::with_name("extract")
-                    .long("extract")
-                    .short("e")
-                    .alias("archive")
-                    .alias("arch")
-                    .alias("a")
-                    .help("Extract an archived file"),
+                Arg::with_name("clip")
+                    .long("clip")
+                    .short("c")
+                    .alias("output-to-clipboard")
+                    .help("Copy the contents of small text files to your clipboard"),
             )
         }
 
@@ -24,5 +24,42 @@ impl CmdHistory {
                     .alias("flush")
                     .help("Clear all history"),
             )
+            .arg(
+                Arg::with_name("export")
+                    .long("export")
+                    .conflicts_with_all(&["rm", "clear", "import"])
+                    .help("Export history, including secrets, to stdout"),
+            )
+            .arg(
+                Arg::with_name("import")
+                    .long("import")
+                    .value_name("FILE")
+                    .conflicts_with_all(&["rm", "clear"])
+                    .help("Import and merge history exported on another machine"),
+            )
+            .arg(
+                Arg::with_name("check")
+                    .long("check")
+                    .conflicts_with_all(&["rm", "clear", "export", "import"])
+                    .help("Check history entries for corruption"),
+            )
+            .arg(
+                Arg::with_name("probe")
+                    .long("probe")
+                    .requires("check")
+                    .help("Also check whether each file still exists on the server"),
+            )
+            .arg(
+                Arg::with_name("fix")
+                    .long("fix")
+                    .requires("check")
+                    .help("Remove corrupt or dead entries when checking"),
+            )
+            .arg(
+                Arg::with_name("replace")
+                    .long("replace")
+                    .requires("import")
+                    .help("Replace current history with imported history"),
+            )
     }
 }
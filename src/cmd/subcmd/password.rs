@@ -1,6 +1,6 @@
 use clap::{App, SubCommand};
 
-use crate::cmd::arg::{ArgGenPassphrase, ArgOwner, ArgPassword, ArgUrl, CmdArg};
+use crate::cmd::arg::{ArgCheckOwner, ArgGenPassphrase, ArgOwner, ArgPassword, ArgUrl, CmdArg};
 
 /// The password command definition.
 pub struct CmdPassword;
@@ -15,5 +15,6 @@ impl CmdPassword {
             .arg(ArgPassword::build().help("Specify a password, do not prompt"))
             .arg(ArgGenPassphrase::build())
             .arg(ArgOwner::build())
+            .arg(ArgCheckOwner::build())
     }
 }
@@ -1,7 +1,11 @@
 use clap::{App, Arg, SubCommand};
 use ffsend_api::action::params::PARAMS_DEFAULT_DOWNLOAD_STR as DOWNLOAD_DEFAULT;
+#[cfg(feature = "archive")]
+use glob::Pattern;
 
 use crate::cmd::arg::{ArgDownloadLimit, ArgGenPassphrase, ArgHost, ArgPassword, CmdArg};
+use crate::host::parse_host;
+use crate::util::parse_size;
 
 /// The upload command definition.
 pub struct CmdUpload;
@@ -16,7 +20,7 @@ impl CmdUpload {
             .visible_alias("up")
             .arg(
                 Arg::with_name("FILE")
-                    .help("The file(s) to upload")
+                    .help("The file(s) to upload, '-' to read from stdin")
                     .required(true)
                     .multiple(true),
             )
@@ -30,26 +34,127 @@ impl CmdUpload {
                     .short("n")
                     .alias("file")
                     .alias("f")
+                    .alias("archive-name")
                     .value_name("NAME")
                     .help("Rename the file being uploaded"),
             )
+            .arg(
+                Arg::with_name("name-fallback")
+                    .long("name-fallback")
+                    .value_name("MODE")
+                    .possible_values(&["file", "parent", "require"])
+                    .hide_possible_values(true)
+                    .help("Name to use if it can't be derived: file, parent or require"),
+            )
             .arg(
                 Arg::with_name("open")
                     .long("open")
                     .short("o")
                     .help("Open the share link in your browser"),
+            )
+            .arg(
+                Arg::with_name("no-secret-url")
+                    .long("no-secret-url")
+                    .help("Show the share link and it's secret separately"),
+            )
+            .arg(
+                Arg::with_name("webhook")
+                    .long("webhook")
+                    .value_name("URL")
+                    .help("Post the uploaded file details to a webhook")
+                    .validator(|arg| {
+                        parse_host(&arg)
+                            .map(|_| ())
+                            .map_err(|err| format!("{}", err))
+                    }),
+            )
+            .arg(
+                Arg::with_name("webhook-owner")
+                    .long("webhook-owner")
+                    .requires("webhook")
+                    .help("Include the owner token in the webhook details"),
+            )
+            .arg(
+                Arg::with_name("explain")
+                    .long("explain")
+                    .visible_alias("dry-run")
+                    .help("Explain the upload request that would be made, without uploading")
+                    .long_help(
+                        "Explain the upload request that would be made, without uploading.\n\n\
+                         Nothing is encrypted or sent, the file is only inspected on disk. The \
+                         encrypted size is an estimate, computed from the file size.",
+                    ),
+            )
+            .arg(
+                Arg::with_name("html")
+                    .long("html")
+                    .help("Print an HTML snippet linking to the file")
+                    .long_help(
+                        "Print an HTML snippet linking to the file.\n\n\
+                         With --json, the snippet is included as 'html' in the output object.",
+                    ),
+            )
+            .arg(
+                Arg::with_name("split")
+                    .long("split")
+                    .value_name("SIZE")
+                    .help("Split the file into multiple uploads of this size")
+                    .validator(|arg| match parse_size(&arg) {
+                        Some(size) if size > 0 => Ok(()),
+                        _ => Err(String::from(
+                            "Split size must be a positive size, such as '500M' or '1G'",
+                        )),
+                    }),
+            )
+            .arg(
+                Arg::with_name("max-size")
+                    .long("max-size")
+                    .value_name("SIZE")
+                    .help("The maximum file size the server allows, if it differs from the default")
+                    .validator(|arg| match parse_size(&arg) {
+                        Some(size) if size > 0 => Ok(()),
+                        _ => Err(String::from(
+                            "Maximum size must be a positive size, such as '1G' or '2500M'",
+                        )),
+                    }),
+            )
+            .arg(
+                Arg::with_name("no-follow")
+                    .long("no-follow")
+                    .alias("no-follow-symlinks")
+                    .help("Don't follow a symbolic link given as file to upload"),
             );
 
         // Optional archive support
         #[cfg(feature = "archive")]
         {
-            cmd = cmd.arg(
-                Arg::with_name("archive")
-                    .long("archive")
-                    .short("a")
-                    .alias("arch")
-                    .help("Archive the upload in a single file"),
-            )
+            cmd = cmd
+                .arg(
+                    Arg::with_name("archive")
+                        .long("archive")
+                        .short("a")
+                        .alias("arch")
+                        .help("Archive the upload in a single file"),
+                )
+                .arg(
+                    Arg::with_name("dereference")
+                        .long("dereference")
+                        .alias("follow-symlinks")
+                        .help("Follow symbolic links inside archived directories"),
+                )
+                .arg(
+                    Arg::with_name("exclude")
+                        .long("exclude")
+                        .value_name("GLOB")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Exclude matching files from archived directories")
+                        .validator(|arg| {
+                            Pattern::new(&arg)
+                                .map(|_| ())
+                                .map_err(|err| format!("Invalid exclude pattern: {}", err))
+                        }),
+                )
         }
 
         // Optional clipboard support
@@ -70,6 +175,16 @@ impl CmdUpload {
                         .short("C")
                         .help("Copy the ffsend download command to your clipboard")
                         .conflicts_with("copy"),
+                )
+                .arg(
+                    Arg::with_name("copy-owner")
+                        .long("copy-owner")
+                        .help("Copy the owner token to your clipboard")
+                        .long_help(
+                            "Copy the owner token to your clipboard.\n\n\
+                             May be combined with --copy or --copy-cmd, the owner token is then \
+                             copied on a second line.",
+                        ),
                 );
         }
 
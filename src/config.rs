@@ -10,6 +10,31 @@ pub const CLIENT_TIMEOUT: u64 = 30;
 /// Make sure this is big enough, or file uploads will be dropped. `0` to disable.
 pub const CLIENT_TRANSFER_TIMEOUT: u64 = 24 * 60 * 60;
 
+/// The delay in seconds before the first retry of a failed transfer, doubled for each retry.
+pub const RETRY_DELAY_BASE: u64 = 2;
+
+/// The maximum delay in seconds before retrying a failed transfer.
+pub const RETRY_DELAY_MAX: u64 = 60;
+
+/// The timeout for webhook notification requests.
+pub const WEBHOOK_TIMEOUT: u64 = 10;
+
+/// The maximum size in bytes of a downloaded file to allow copying to the clipboard.
+#[cfg(feature = "clipboard")]
+pub const CLIPBOARD_SIZE_MAX: u64 = 64 * 1024;
+
+/// The maximum total size in bytes of the contents extracted from a downloaded archive by default.
+#[cfg(feature = "archive")]
+pub const EXTRACT_SIZE_MAX: u64 = 16 * 1024 * 1024 * 1024;
+
+/// The maximum number of entries to extract from a downloaded archive by default.
+#[cfg(feature = "archive")]
+pub const EXTRACT_ENTRIES_MAX: usize = 100_000;
+
+/// The name to share an archive of multiple files with, if no name is given.
+#[cfg(feature = "archive")]
+pub const ARCHIVE_NAME_DEFAULT: &str = "archive";
+
 /// The default desired version to select for the server API.
 pub const API_VERSION_DESIRED_DEFAULT: DesiredVersion = DesiredVersion::Assume(API_VERSION_ASSUME);
 
@@ -0,0 +1,287 @@
+//! Loading of configuration files, used to configure defaults through `--config`.
+
+extern crate toml;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Error as IoError;
+use std::path::Path;
+use std::sync::RwLock;
+
+use clap::ArgMatches;
+
+use self::toml::de::Error as DeError;
+use self::toml::value::{Table, Value};
+
+/// The key of the table holding named profiles in the configuration file.
+const PROFILES_KEY: &str = "profile";
+
+/// The keys that may be configured, and the kind of value each of them takes.
+const KEYS: &[(&str, Kind)] = &[
+    ("allow-http", Kind::Flag),
+    ("api", Kind::Text),
+    ("archive", Kind::Flag),
+    ("basic-auth", Kind::Text),
+    ("clip", Kind::Flag),
+    ("copy", Kind::Flag),
+    ("copy-cmd", Kind::Flag),
+    ("copy-owner", Kind::Flag),
+    ("extract", Kind::Flag),
+    ("force", Kind::Flag),
+    ("history", Kind::Text),
+    ("host", Kind::Text),
+    ("incognito", Kind::Flag),
+    ("json", Kind::Flag),
+    ("metrics-file", Kind::Text),
+    ("metrics-format", Kind::Text),
+    ("no-interact", Kind::Flag),
+    ("no-progress", Kind::Flag),
+    ("open", Kind::Flag),
+    ("password", Kind::Text),
+    ("progress-socket", Kind::Text),
+    ("quiet", Kind::Flag),
+    ("rename-on-conflict", Kind::Flag),
+    ("retries", Kind::Number),
+    ("si", Kind::Flag),
+    ("stall-timeout", Kind::Number),
+    ("timeout", Kind::Number),
+    ("transfer-timeout", Kind::Number),
+    ("verbose", Kind::Flag),
+    ("yes", Kind::Flag),
+];
+
+lazy_static! {
+    /// The settings loaded from the configuration file, empty if none was loaded.
+    static ref SETTINGS: RwLock<Settings> = RwLock::new(Settings::default());
+}
+
+/// The kind of value a configuration key takes.
+#[derive(Clone, Copy)]
+enum Kind {
+    /// A flag, enabled by setting it to `true`.
+    Flag,
+
+    /// A positive number.
+    Number,
+
+    /// Any string.
+    Text,
+}
+
+/// Settings loaded from a configuration file.
+///
+/// These are kept in memory, and are only used as defaults for flags and options that aren't given
+/// on the command line or through the environment. They're never exported to the environment, so
+/// secrets such as a password don't leak to commands we spawn.
+#[derive(Debug, Default, PartialEq)]
+pub struct Settings {
+    /// The configured option values.
+    values: HashMap<String, String>,
+
+    /// The enabled flags.
+    flags: Vec<String>,
+}
+
+impl Settings {
+    /// Load the settings from the configuration file at the given path.
+    ///
+    /// Each key configures the flag or option of the same name, such as `host` for `--host` or
+    /// `transfer-timeout` for `--transfer-timeout`. Boolean flags are enabled by setting them to
+    /// `true`. Unknown keys and values of the wrong type are refused.
+    ///
+    /// Named profiles may be defined in `[profile.<name>]` tables. If a `profile` is given, it's
+    /// values are applied over the values at the top of the file.
+    pub fn load(path: &Path, profile: Option<&str>) -> Result<Self, Error> {
+        Self::parse(&fs::read_to_string(path)?, profile)
+    }
+
+    /// Parse the settings from the given configuration file contents, see `load`.
+    fn parse(data: &str, profile: Option<&str>) -> Result<Self, Error> {
+        let mut config: Table = toml::from_str(data)?;
+
+        // Take the named profiles from the configuration
+        let mut profiles = match config.remove(PROFILES_KEY) {
+            Some(Value::Table(profiles)) => profiles,
+            Some(_) => return Err(Error::Value(PROFILES_KEY.into())),
+            None => Table::new(),
+        };
+
+        // Apply the values of the selected profile over the top level values
+        if let Some(profile) = profile {
+            match profiles.remove(profile) {
+                Some(Value::Table(values)) => config.extend(values),
+                Some(_) => return Err(Error::Value(format!("{}.{}", PROFILES_KEY, profile))),
+                None => {
+                    return Err(Error::UnknownProfile(
+                        profile.into(),
+                        profiles.keys().cloned().collect::<Vec<_>>().join(", "),
+                    ))
+                }
+            }
+        }
+
+        let mut settings = Settings::default();
+        for (key, value) in config {
+            let kind = match KEYS.iter().find(|(name, _)| *name == key) {
+                Some((_, kind)) => *kind,
+                None => return Err(Error::UnknownKey(key)),
+            };
+
+            match (kind, value) {
+                (Kind::Flag, Value::Boolean(true)) => settings.flags.push(key),
+                (Kind::Flag, Value::Boolean(false)) => {}
+                (Kind::Number, Value::Integer(value)) if value >= 0 => {
+                    settings.values.insert(key, value.to_string());
+                }
+                (Kind::Text, Value::String(value)) => {
+                    settings.values.insert(key, value);
+                }
+                _ => return Err(Error::Value(key)),
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Use these settings as defaults for the rest of the program.
+    pub fn apply(self) {
+        *SETTINGS.write().unwrap() = self;
+    }
+}
+
+/// Get the value of the option with the given `name`.
+///
+/// A value given on the command line or through the environment takes precedence over the
+/// configuration file, which in turn takes precedence over the default value of the option.
+pub fn value<'a>(matches: &'a ArgMatches, name: &str) -> Option<Cow<'a, str>> {
+    if matches.occurrences_of(name) == 0 && env::var_os(env_var(name)).is_none() {
+        if let Some(value) = SETTINGS.read().unwrap().values.get(name) {
+            return Some(Cow::Owned(value.clone()));
+        }
+    }
+    matches.value_of(name).map(Cow::Borrowed)
+}
+
+/// Check whether the flag with the given `name` is enabled in the configuration file.
+pub fn flag(name: &str) -> bool {
+    SETTINGS
+        .read()
+        .unwrap()
+        .flags
+        .iter()
+        .any(|flag| flag == name)
+}
+
+/// Get the name of the environment variable for the option with the given `name`.
+fn env_var(name: &str) -> String {
+    format!("FFSEND_{}", name.replace('-', "_").to_uppercase())
+}
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// Failed to read the configuration file.
+    #[fail(display = "failed to read the configuration file")]
+    Read(#[cause] IoError),
+
+    /// Failed to parse the configuration file.
+    #[fail(display = "failed to parse the configuration file")]
+    Parse(#[cause] DeError),
+
+    /// The selected profile is not defined in the configuration file.
+    #[fail(display = "unknown profile '{}', available profiles: {}", _0, _1)]
+    UnknownProfile(String, String),
+
+    /// The configuration file contains an unknown key.
+    #[fail(display = "unknown key '{}' in the configuration file", _0)]
+    UnknownKey(String),
+
+    /// A configuration value has the wrong type.
+    #[fail(display = "invalid value for '{}' in the configuration file", _0)]
+    Value(String),
+}
+
+impl From<IoError> for Error {
+    fn from(err: IoError) -> Self {
+        Error::Read(err)
+    }
+}
+
+impl From<DeError> for Error {
+    fn from(err: DeError) -> Self {
+        Error::Parse(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        host = "https://send.example.com/"
+        timeout = 10
+        quiet = true
+        force = false
+
+        [profile.work]
+        host = "https://send.work.example.com/"
+        yes = true
+    "#;
+
+    #[test]
+    fn parse_values() {
+        let settings = Settings::parse(CONFIG, None).unwrap();
+        assert_eq!(settings.values["host"], "https://send.example.com/");
+        assert_eq!(settings.values["timeout"], "10");
+        assert_eq!(settings.flags, vec!["quiet".to_owned()]);
+    }
+
+    #[test]
+    fn parse_profile() {
+        let settings = Settings::parse(CONFIG, Some("work")).unwrap();
+        assert_eq!(settings.values["host"], "https://send.work.example.com/");
+        assert_eq!(settings.values["timeout"], "10");
+        assert!(settings.flags.contains(&"yes".to_owned()));
+    }
+
+    #[test]
+    fn parse_unknown_profile() {
+        match Settings::parse(CONFIG, Some("home")) {
+            Err(Error::UnknownProfile(profile, available)) => {
+                assert_eq!(profile, "home");
+                assert_eq!(available, "work");
+            }
+            _ => panic!("expected an unknown profile error"),
+        }
+    }
+
+    #[test]
+    fn parse_unknown_key() {
+        match Settings::parse("hots = \"https://send.example.com/\"", None) {
+            Err(Error::UnknownKey(key)) => assert_eq!(key, "hots"),
+            _ => panic!("expected an unknown key error"),
+        }
+    }
+
+    #[test]
+    fn parse_invalid_values() {
+        for data in &[
+            "quiet = \"yes\"",
+            "timeout = \"10\"",
+            "timeout = -1",
+            "host = 1",
+        ] {
+            match Settings::parse(data, None) {
+                Err(Error::Value(_)) => {}
+                _ => panic!("expected an invalid value error for: {}", data),
+            }
+        }
+    }
+
+    #[test]
+    fn env_var_names() {
+        assert_eq!(env_var("host"), "FFSEND_HOST");
+        assert_eq!(env_var("transfer-timeout"), "FFSEND_TRANSFER_TIMEOUT");
+    }
+}
@@ -1,5 +1,6 @@
 use ffsend_api::action::delete::Error as DeleteError;
 use ffsend_api::action::exists::Error as ExistsError;
+use ffsend_api::action::info::Error as InfoError;
 use ffsend_api::action::params::Error as ParamsError;
 use ffsend_api::action::password::Error as PasswordError;
 use ffsend_api::action::version::Error as VersionError;
@@ -70,6 +71,10 @@ pub enum ActionError {
     #[fail(display = "failed to fetch file info")]
     Info(#[cause] CliInfoError),
 
+    /// The owner token could not be verified before modifying the file.
+    #[fail(display = "failed to verify the owner token, it may be invalid")]
+    OwnerInvalid(#[cause] InfoError),
+
     /// An error occurred while invoking the params action.
     #[fail(display = "failed to change the parameters")]
     Params(#[cause] ParamsError),
@@ -1,17 +1,21 @@
 extern crate toml;
 extern crate version_compare;
 
+use std::collections::HashMap;
 use std::fs;
-use std::io::Error as IoError;
-use std::path::PathBuf;
+use std::io::{Error as IoError, Write};
+use std::path::{Path, PathBuf};
 
 use self::toml::de::Error as DeError;
 use self::toml::ser::Error as SerError;
+use self::toml::value::{Table, Value};
 use self::version_compare::{CompOp, VersionCompare};
 use failure::Fail;
 use ffsend_api::file::remote_file::{FileParseError, RemoteFile};
 use ffsend_api::url::Url;
+use tempfile::NamedTempFile;
 
+use crate::secret_store::{self, SecretStore, SECRET_FIELDS};
 use crate::util::{print_error, print_warning};
 
 /// The minimum supported history file version.
@@ -36,6 +40,20 @@ pub struct History {
     /// An optional path to automatically save the history to.
     #[serde(skip)]
     autosave: Option<PathBuf>,
+
+    /// The store to keep the secrets of files in, `None` to keep them in the history file.
+    #[serde(skip)]
+    secrets: Option<Box<dyn SecretStore>>,
+
+    /// The secret data of files having their secrets in the secret store, by file ID.
+    #[serde(skip)]
+    stored: HashMap<String, String>,
+
+    /// Files of which the secrets couldn't be retrieved from the secret store, as parsed TOML.
+    ///
+    /// These can't be used, but are written back as is when saving to not lose them.
+    #[serde(skip)]
+    unavailable: Vec<Value>,
 }
 
 impl History {
@@ -44,6 +62,7 @@ impl History {
     pub fn new(autosave: Option<PathBuf>) -> Self {
         let mut history = History::default();
         history.autosave = autosave;
+        history.secrets = secret_store::default_store();
         history
     }
 
@@ -52,10 +71,88 @@ impl History {
         // Read the file to a string
         let data = fs::read_to_string(&path)?;
 
-        // Parse the data, set the autosave path
-        let mut history: Self = toml::from_str(&data)?;
+        // Parse the data, restore secrets kept apart, set the autosave path
+        let secrets = secret_store::default_store();
+        let mut history = match secrets {
+            Some(ref store) => Self::parse_restore(&data, &**store)?,
+            None => Self::parse(&data)?,
+        };
         history.autosave = Some(path);
+        history.secrets = secrets;
+
+        // Garbage collect
+        history.gc();
+
+        Ok(history)
+    }
+
+    /// Parse a history from the given serialized data, and check it's version.
+    fn parse(data: &str) -> Result<Self, LoadError> {
+        Ok(Self::check_version(toml::from_str(data)?))
+    }
 
+    /// Parse a history from the given serialized data, and restore the secrets of files from the
+    /// given secret store.
+    ///
+    /// Files having their secrets in the history file itself are kept as is. Files of which the
+    /// secrets are missing from the store can't be used anymore, and are dropped with a warning.
+    /// Files of which the secrets couldn't be retrieved are skipped with a warning, and are kept
+    /// apart to write them back when saving.
+    fn parse_restore(data: &str, store: &dyn SecretStore) -> Result<Self, LoadError> {
+        let mut value: Value = toml::from_str(data)?;
+        let mut stored = HashMap::new();
+        let mut missing = Vec::new();
+        let mut unavailable = Vec::new();
+
+        if let Some(files) = value.get_mut("files").and_then(Value::as_array_mut) {
+            for file in files.iter_mut().filter_map(Value::as_table_mut) {
+                let id = match file.get("id").and_then(Value::as_str) {
+                    Some(_) if SECRET_FIELDS.iter().any(|f| file.contains_key(*f)) => continue,
+                    Some(id) => id.to_owned(),
+                    None => continue,
+                };
+                match store.retrieve(&id) {
+                    Ok(Some(data)) => {
+                        let secrets: Table = toml::from_str(&data)?;
+                        for (field, value) in secrets {
+                            file.insert(field, value);
+                        }
+                        stored.insert(id, data);
+                    }
+                    Ok(None) => missing.push(id),
+                    Err(err) => {
+                        print_error(err.context(format!(
+                            "failed to retrieve secrets of file {}, skipping it",
+                            id,
+                        )));
+                        unavailable.push(Value::Table(file.clone()));
+                    }
+                }
+            }
+            files.retain(|file| match file.get("id").and_then(Value::as_str) {
+                Some(id) => {
+                    !missing.iter().any(|m| m == id)
+                        && !unavailable.iter().any(|f| file_id(f) == id)
+                }
+                None => true,
+            });
+        }
+
+        for id in &missing {
+            print_warning(format!(
+                "secrets of file {} are missing from the secret store, removing it from history",
+                id,
+            ));
+        }
+
+        let mut history = Self::check_version(value.try_into()?);
+        history.stored = stored;
+        history.unavailable = unavailable;
+        Ok(history)
+    }
+
+    /// Check the version of the given parsed history, warn if it isn't supported.
+    fn check_version(mut history: Self) -> Self {
         // Make sure the file version is supported
         if history.version.is_none() {
             print_warning("History file has no version, ignoring");
@@ -71,10 +168,7 @@ impl History {
             }
         }
 
-        // Garbage collect
-        history.gc();
-
-        Ok(history)
+        history
     }
 
     /// Load the history from the given file.
@@ -98,7 +192,11 @@ impl History {
         let path = self.autosave.as_ref().ok_or(SaveError::NoPath)?;
 
         // If we have no files, remove the history file if it exists
-        if self.files.is_empty() {
+        if self.files.is_empty() && self.unavailable.is_empty() {
+            if let Some(ref store) = self.secrets {
+                self.delete_secrets(&**store, &HashMap::new());
+            }
+            self.stored.clear();
             if path.is_file() {
                 fs::remove_file(&path).map_err(SaveError::Delete)?;
             }
@@ -110,29 +208,120 @@ impl History {
             fs::create_dir_all(parent)?;
         }
 
-        // Set file permissions on unix based systems
-        #[cfg(unix)]
-        {
-            use std::fs::Permissions;
-            use std::os::unix::fs::PermissionsExt;
+        // Build the data, write it so an interrupted save never corrupts the existing history
+        let data = match self.secrets {
+            Some(ref store) => {
+                let (data, stored) = self.serialize_apart(&**store)?;
+                self.stored = stored;
+                data
+            }
+            None => toml::to_string(self)?,
+        };
+        write_atomic(path, |file| file.write_all(data.as_bytes()))?;
+
+        // There are no new changes, set the flag
+        self.changed = false;
 
-            if !path.exists() {
-                let file = fs::File::create(path).map_err(SaveError::Write)?;
+        Ok(())
+    }
 
-                // Set Read/Write permissions for the user
-                file.set_permissions(Permissions::from_mode(0o600))
-                    .map_err(SaveError::SetPermissions)?;
+    /// Serialize the history, and move the secrets of files into the given secret store.
+    ///
+    /// Only secrets that changed since they were last stored are written to the store. Files of
+    /// which the secrets couldn't be stored keep them in the history file, with a warning. The
+    /// serialized history and the secret data of files having their secrets stored are returned.
+    fn serialize_apart(
+        &self,
+        store: &dyn SecretStore,
+    ) -> Result<(String, HashMap<String, String>), SaveError> {
+        let mut value = Value::try_from(self)?;
+        let mut stored = HashMap::new();
+
+        if let Some(files) = value.get_mut("files").and_then(Value::as_array_mut) {
+            for file in files.iter_mut().filter_map(Value::as_table_mut) {
+                let id = match file.get("id").and_then(Value::as_str) {
+                    Some(id) => id.to_owned(),
+                    None => continue,
+                };
+
+                let mut secrets = Table::new();
+                for field in SECRET_FIELDS {
+                    if let Some(value) = file.get(*field) {
+                        secrets.insert((*field).into(), value.clone());
+                    }
+                }
+
+                let data = toml::to_string(&secrets)?;
+                let result = match self.stored.get(&id) {
+                    Some(previous) if *previous == data => Ok(()),
+                    _ => store.store(&id, &data),
+                };
+                match result {
+                    Ok(()) => {
+                        for field in SECRET_FIELDS {
+                            file.remove(*field);
+                        }
+                        stored.insert(id, data);
+                    }
+                    Err(err) => print_error(err.context(format!(
+                        "failed to store secrets of file {}, keeping them in the history file",
+                        id,
+                    ))),
+                }
             }
+
+            // Write back files of which the secrets were unavailable, unless added again
+            let ids: Vec<String> = files.iter().map(|file| file_id(file).to_owned()).collect();
+            files.extend(
+                self.unavailable
+                    .iter()
+                    .filter(|file| !ids.iter().any(|id| id == file_id(file)))
+                    .cloned(),
+            );
         }
 
-        // Build the data and write to a file
-        let data = toml::to_string(self)?;
-        fs::write(&path, data)?;
+        self.delete_secrets(store, &stored);
+        Ok((toml::to_string(&value)?, stored))
+    }
 
-        // There are no new changes, set the flag
-        self.changed = false;
+    /// Delete the secrets of files that are no longer stored from the given secret store.
+    ///
+    /// Files in `keep` are kept. Errors are printed and ignored.
+    fn delete_secrets(&self, store: &dyn SecretStore, keep: &HashMap<String, String>) {
+        for id in self.stored.keys().filter(|id| !keep.contains_key(*id)) {
+            if let Err(err) = store.delete(id) {
+                print_error(err.context("failed to delete secrets of removed file, ignoring"));
+            }
+        }
+    }
 
-        Ok(())
+    /// Export the history, to import it on another machine.
+    ///
+    /// The export uses the versioned history file format, and includes all secrets and owner
+    /// tokens.
+    pub fn export(&self) -> Result<String, SerError> {
+        toml::to_string(self)
+    }
+
+    /// Import an exported history from the given data.
+    ///
+    /// Imported files are merged with the files in this history, matched by their ID. If `replace`
+    /// is set, all current files are removed first. The number of imported files is returned.
+    pub fn import(&mut self, data: &str, replace: bool) -> Result<usize, LoadError> {
+        let mut imported = Self::parse(data)?;
+        imported.gc();
+
+        if replace {
+            self.clear();
+        }
+
+        let files = imported.files.split_off(0);
+        let count = files.len();
+        for file in files {
+            self.add(file, false);
+        }
+
+        Ok(count)
     }
 
     /// Add the given remote file to the history.
@@ -163,6 +352,13 @@ impl History {
             }
         }
 
+        // Warn if another file has the same owner token, managing one could affect the other
+        if let Some(token) = file.owner_token() {
+            if self.files.iter().any(|f| f.owner_token() == Some(token)) {
+                print_warning("another file in history has the same owner token");
+            }
+        }
+
         // Add the file to the list
         self.files.push(file);
         self.changed = true;
@@ -193,6 +389,24 @@ impl History {
         !expired_indices.is_empty()
     }
 
+    /// Remove the files at the given positions, as listed by `files`.
+    ///
+    /// Other files having the same ID are kept. The number of removed files is returned.
+    pub fn remove_at(&mut self, indices: &[usize]) -> usize {
+        let count = self.files.len();
+        let mut i = 0;
+        self.files.retain(|_| {
+            i += 1;
+            !indices.contains(&(i - 1))
+        });
+
+        let removed = count - self.files.len();
+        if removed > 0 {
+            self.changed = true;
+        }
+        removed
+    }
+
     /// Remove a file by the given URL.
     ///
     /// If any file was removed, true is returned.
@@ -217,8 +431,9 @@ impl History {
 
     /// Clear all history.
     pub fn clear(&mut self) {
-        self.changed = !self.files.is_empty();
+        self.changed = !self.files.is_empty() || !self.unavailable.is_empty();
         self.files.clear();
+        self.unavailable.clear();
     }
 
     /// Garbage collect (remove) all files that have been expired,
@@ -251,6 +466,49 @@ impl History {
     }
 }
 
+/// Get the ID of the given file in history, as parsed TOML.
+///
+/// An empty string is returned if the file has no ID.
+fn file_id(file: &Value) -> &str {
+    file.get("id").and_then(Value::as_str).unwrap_or("")
+}
+
+/// Replace the file at `path` with the data written by `write`.
+///
+/// The data is written to a temporary file next to it first, which is atomically moved in place
+/// once fully written. If writing fails, the existing file is left intact and the temporary file
+/// is removed.
+fn write_atomic<F>(path: &Path, write: F) -> Result<(), SaveError>
+where
+    F: FnOnce(&mut NamedTempFile) -> Result<(), IoError>,
+{
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut file = NamedTempFile::new_in(dir)?;
+
+    // Set file permissions on unix based systems
+    #[cfg(unix)]
+    {
+        use std::fs::Permissions;
+        use std::os::unix::fs::PermissionsExt;
+
+        // Set Read/Write permissions for the user
+        file.as_file()
+            .set_permissions(Permissions::from_mode(0o600))
+            .map_err(SaveError::SetPermissions)?;
+    }
+
+    // Write the data and flush it to disk, then atomically move the file in place
+    write(&mut file)?;
+    file.as_file().sync_all()?;
+    file.persist(path)
+        .map_err(|err| SaveError::Write(err.error))?;
+
+    Ok(())
+}
+
 impl Drop for History {
     fn drop(&mut self) {
         // Automatically save if enabled and something was changed
@@ -270,6 +528,9 @@ impl Default for History {
             files: Vec::new(),
             changed: false,
             autosave: None,
+            secrets: None,
+            stored: HashMap::new(),
+            unavailable: Vec::new(),
         }
     }
 }
@@ -355,3 +616,283 @@ impl From<IoError> for SaveError {
         SaveError::Write(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::secret_store::MemoryStore;
+
+    /// Build a remote file for the given file ID.
+    fn file(id: &str) -> RemoteFile {
+        let url = Url::parse(&format!(
+            "https://send.example.com/download/{}/#AAAAAAAAAAAAAAAAAAAAAA",
+            id
+        ))
+        .unwrap();
+        RemoteFile::parse_url(url, None).unwrap()
+    }
+
+    /// Build a history with the given files, without autosaving.
+    fn history(files: Vec<RemoteFile>) -> History {
+        let mut history = History::new(None);
+        history.files = files;
+        history
+    }
+
+    #[test]
+    fn write_atomic_replaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+        fs::write(&path, "old").unwrap();
+
+        write_atomic(&path, |file| file.write_all(b"new")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn write_atomic_failed_keeps_old() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+        fs::write(&path, "old").unwrap();
+
+        // Fail halfway through writing the new history
+        let result = write_atomic(&path, |file| {
+            file.write_all(b"partial")?;
+            Err(IoError::new(std::io::ErrorKind::Other, "disk full"))
+        });
+        assert!(result.is_err());
+
+        // The old history is intact, and no temporary file is left behind
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn remove_at_keeps_same_id() {
+        let mut history = history(vec![
+            file("abc12345"),
+            file("def12345"),
+            file("abc12345"),
+            file("abc12345"),
+        ]);
+        assert_eq!(history.remove_at(&[2, 3]), 2);
+        let ids: Vec<&str> = history.files().iter().map(|f| f.id()).collect();
+        assert_eq!(ids, vec!["abc12345", "def12345"]);
+        assert!(history.changed);
+    }
+
+    #[test]
+    fn remove_at_nothing() {
+        let mut history = history(vec![file("abc12345")]);
+        assert_eq!(history.remove_at(&[]), 0);
+        assert_eq!(history.remove_at(&[5]), 0);
+        assert_eq!(history.files().len(), 1);
+        assert!(!history.changed);
+    }
+
+    /// Get the IDs of the files in the given history.
+    fn ids(history: &History) -> Vec<&str> {
+        history.files().iter().map(|f| f.id()).collect()
+    }
+
+    #[test]
+    fn import_merges() {
+        let data = history(vec![file("abc12345"), file("def12345")])
+            .export()
+            .unwrap();
+        let mut history = history(vec![file("abc12345"), file("ghi12345")]);
+        assert_eq!(history.import(&data, false).unwrap(), 2);
+        assert_eq!(ids(&history), vec!["abc12345", "ghi12345", "def12345"]);
+        assert!(history.changed);
+    }
+
+    #[test]
+    fn import_replaces() {
+        let data = history(vec![file("abc12345"), file("def12345")])
+            .export()
+            .unwrap();
+        let mut history = history(vec![file("ghi12345")]);
+        assert_eq!(history.import(&data, true).unwrap(), 2);
+        assert_eq!(ids(&history), vec!["abc12345", "def12345"]);
+    }
+
+    #[test]
+    fn import_invalid() {
+        let mut history = history(vec![file("abc12345")]);
+        assert!(history.import("files = 1", false).is_err());
+        assert!(history.import("not toml", true).is_err());
+        assert_eq!(ids(&history), vec!["abc12345"]);
+    }
+
+    /// Build a history saving to `path`, with a file having an owner token, keeping secrets in
+    /// the given store.
+    fn history_apart(path: &Path, store: &MemoryStore) -> History {
+        let url = Url::parse("https://send.example.com/download/abc12345/#AAAAAAAAAAAAAAAAAAAAAA");
+        let mut history = History::new(Some(path.to_path_buf()));
+        history.secrets = Some(Box::new(store.clone()));
+        history.add(
+            RemoteFile::parse_url(url.unwrap(), Some("token123".into())).unwrap(),
+            false,
+        );
+        history
+    }
+
+    /// Get the first file in the history saved at `path`, as parsed TOML table.
+    fn saved_file(path: &Path) -> Table {
+        let value: Value = toml::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        value["files"][0].as_table().unwrap().clone()
+    }
+
+    #[test]
+    fn save_secrets_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+        let store = MemoryStore::default();
+        let mut history = history_apart(&path, &store);
+        history.save().unwrap();
+
+        // The history file holds no secrets, the store does
+        let file = saved_file(&path);
+        assert_eq!(file["id"].as_str(), Some("abc12345"));
+        assert!(!file.contains_key("secret"));
+        assert!(!file.contains_key("owner_token"));
+        assert!(store.data.borrow()["abc12345"].contains("token123"));
+
+        // Secrets are restored when loading
+        let data = fs::read_to_string(&path).unwrap();
+        let loaded = History::parse_restore(&data, &store).unwrap();
+        let file = &loaded.files()[0];
+        assert_eq!(file.owner_token().map(|t| t.as_str()), Some("token123"));
+        assert_eq!(
+            file.download_url(true).as_str(),
+            "https://send.example.com/download/abc12345/#AAAAAAAAAAAAAAAAAAAAAA",
+        );
+        assert_eq!(loaded.stored.keys().collect::<Vec<_>>(), vec!["abc12345"]);
+    }
+
+    #[test]
+    fn save_secrets_deletes_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+        let store = MemoryStore::default();
+        let mut history = history_apart(&path, &store);
+        history.save().unwrap();
+        history.add(file("def12345"), false);
+        history.remove("abc12345");
+        history.save().unwrap();
+        assert!(!store.data.borrow().contains_key("abc12345"));
+        assert!(store.data.borrow().contains_key("def12345"));
+
+        // Secrets are deleted along with the history file
+        history.clear();
+        history.save().unwrap();
+        assert!(store.data.borrow().is_empty());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn save_secrets_store_broken() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+        let store = MemoryStore {
+            broken: true,
+            ..MemoryStore::default()
+        };
+        let mut history = history_apart(&path, &store);
+        history.save().unwrap();
+
+        // Secrets are kept in the history file instead
+        let file = saved_file(&path);
+        assert!(file.contains_key("secret"));
+        assert_eq!(file["owner_token"].as_str(), Some("token123"));
+        assert!(history.stored.is_empty());
+    }
+
+    #[test]
+    fn parse_restore_inline_and_missing() {
+        let data = toml::to_string(&history(vec![file("abc12345"), file("def12345")])).unwrap();
+        let mut value: Value = toml::from_str(&data).unwrap();
+        for field in SECRET_FIELDS {
+            value["files"][1].as_table_mut().unwrap().remove(*field);
+        }
+        let data = toml::to_string(&value).unwrap();
+
+        // Inline secrets are kept, files without secrets anywhere are dropped
+        let store = MemoryStore::default();
+        let loaded = History::parse_restore(&data, &store).unwrap();
+        assert_eq!(ids(&loaded), vec!["abc12345"]);
+        assert!(loaded.stored.is_empty());
+    }
+
+    #[test]
+    fn parse_restore_store_broken() {
+        let data = toml::to_string(&history(vec![file("abc12345")])).unwrap();
+        let mut value: Value = toml::from_str(&data).unwrap();
+        value["files"][0].as_table_mut().unwrap().remove("secret");
+        let data = toml::to_string(&value).unwrap();
+
+        let store = MemoryStore {
+            broken: true,
+            ..MemoryStore::default()
+        };
+
+        // The file is skipped, but kept apart to not lose it
+        let loaded = History::parse_restore(&data, &store).unwrap();
+        assert!(loaded.files().is_empty());
+        assert_eq!(loaded.unavailable.len(), 1);
+        assert_eq!(file_id(&loaded.unavailable[0]), "abc12345");
+    }
+
+    #[test]
+    fn save_keeps_unavailable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+        let store = MemoryStore::default();
+        let mut history = history_apart(&path, &store);
+        history.save().unwrap();
+
+        // Load while the store is unavailable, then add another file and save
+        let data = fs::read_to_string(&path).unwrap();
+        let broken = MemoryStore {
+            broken: true,
+            ..store.clone()
+        };
+        let mut loaded = History::parse_restore(&data, &broken).unwrap();
+        loaded.autosave = Some(path.clone());
+        loaded.secrets = Some(Box::new(store.clone()));
+        loaded.add(file("def12345"), false);
+        loaded.save().unwrap();
+
+        // Both files are in the history file, and the secrets of the skipped one are kept
+        let data = fs::read_to_string(&path).unwrap();
+        let loaded = History::parse_restore(&data, &store).unwrap();
+        assert_eq!(ids(&loaded), vec!["def12345", "abc12345"]);
+        assert!(store.data.borrow()["abc12345"].contains("token123"));
+    }
+
+    #[test]
+    fn save_secrets_changed_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+        let store = MemoryStore::default();
+        let mut history = history_apart(&path, &store);
+        history.save().unwrap();
+
+        // Unchanged secrets aren't stored again
+        store
+            .data
+            .borrow_mut()
+            .insert("abc12345".into(), "untouched".into());
+        history.add(file("def12345"), false);
+        history.save().unwrap();
+        assert_eq!(store.data.borrow()["abc12345"], "untouched");
+        assert!(store.data.borrow().contains_key("def12345"));
+
+        // Changed secrets are
+        history.files[0].set_owner_token(Some("token456".into()));
+        history.save().unwrap();
+        assert!(store.data.borrow()["abc12345"].contains("token456"));
+    }
+}
@@ -1,10 +1,27 @@
+extern crate ctrlc;
+
+use std::path::PathBuf;
+use std::process;
+use std::sync::Mutex;
+
 use failure::Fail;
 use ffsend_api::file::remote_file::RemoteFile;
 
 use crate::cmd::matcher::MainMatcher;
 use crate::history::{Error as HistoryError, History};
+use crate::metrics;
 use crate::util::print_error;
 
+lazy_static! {
+    /// Uploaded files not added to the history yet, along with the history file to add them to.
+    ///
+    /// These are added to the history by the signal handler if interrupted.
+    static ref PENDING: Mutex<Vec<(PathBuf, RemoteFile)>> = Mutex::new(Vec::new());
+}
+
+/// The exit code used when interrupted by a signal.
+const EXIT_INTERRUPTED: i32 = 130;
+
 /// Load the history from the given path, add the given file, and save it
 /// again.
 ///
@@ -23,8 +40,12 @@ fn add_error(
         return Ok(());
     }
 
-    // Load the history, add the file, and save
-    let mut history = History::load_or_new(matcher_main.history())?;
+    add_to(matcher_main.history(), file, overwrite)
+}
+
+/// Load the history from the given path, add the given file, and save it again.
+fn add_to(path: PathBuf, file: RemoteFile, overwrite: bool) -> Result<(), HistoryError> {
+    let mut history = History::load_or_new(path)?;
     history.add(file, overwrite);
     history.save().map_err(|err| err.into())
 }
@@ -38,12 +59,64 @@ fn add_error(
 /// overwrite properties in the already existing file when merging.
 ///
 /// If an error occurred, the error is printed and ignored.
+///
+/// If the file was pending, it isn't anymore.
 pub fn add(matcher_main: &MainMatcher, file: RemoteFile, overwrite: bool) {
+    // Hold the pending lock while adding, so an interruption doesn't add the file twice
+    let mut pending = PENDING.lock().unwrap();
+    pending.retain(|(_, f)| f.id() != file.id());
+
     if let Err(err) = add_error(matcher_main, file, overwrite) {
         print_error(err.context("failed to add file to local history, ignoring"));
     }
 }
 
+/// Mark the given uploaded file as pending to be added to the history.
+///
+/// If interrupted before the file is added with `add`, the signal handler adds it instead.
+pub fn add_pending(matcher_main: &MainMatcher, file: &RemoteFile) {
+    if !matcher_main.incognito() {
+        add_pending_to(matcher_main.history(), file);
+    }
+}
+
+/// Mark the given uploaded file as pending to be added to the history at the given path.
+fn add_pending_to(path: PathBuf, file: &RemoteFile) {
+    PENDING.lock().unwrap().push((path, file.clone()));
+}
+
+/// Stop tracking the given file as pending, without adding it to the history.
+pub fn discard_pending(file: &RemoteFile) {
+    PENDING.lock().unwrap().retain(|(_, f)| f.id() != file.id());
+}
+
+/// Add all pending files to the history.
+///
+/// If an error occurred, the error is printed and ignored.
+fn flush_pending() {
+    let mut pending = PENDING.lock().unwrap();
+    for (path, file) in pending.drain(..) {
+        if let Err(err) = add_to(path, file, false) {
+            print_error(err.context("failed to add file to local history, ignoring"));
+        }
+    }
+}
+
+/// Set up a handler for interrupt and termination signals, adding pending files to the history
+/// before exiting.
+///
+/// If the handler couldn't be set up, the error is printed and ignored.
+pub fn handle_signals() {
+    let result = ctrlc::set_handler(|| {
+        flush_pending();
+        metrics::flush();
+        process::exit(EXIT_INTERRUPTED);
+    });
+    if let Err(err) = result {
+        print_error(err.context("failed to set up signal handler, ignoring"));
+    }
+}
+
 /// Load the history from the given path, remove the given file by it's
 /// ID, and save it again.
 /// True is returned if any file was removed.
@@ -121,3 +194,44 @@ pub fn derive_file_properties(matcher_main: &MainMatcher, file: &mut RemoteFile)
         None => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ffsend_api::url::Url;
+
+    /// Build an uploaded file with the given ID.
+    fn file(id: &str) -> RemoteFile {
+        let url = Url::parse(&format!(
+            "https://send.example.com/download/{}/#c2VjcmV0",
+            id
+        ));
+        RemoteFile::parse_url(url.unwrap(), Some("token123".into())).unwrap()
+    }
+
+    #[test]
+    fn interrupted_after_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+
+        // Interrupted after uploading, before the file is added to the history
+        add_pending_to(path.clone(), &file("abcdef123456"));
+        flush_pending();
+
+        let history = History::load(path).unwrap();
+        let ids: Vec<&str> = history.files().iter().map(|f| f.id()).collect();
+        assert_eq!(ids, vec!["abcdef123456"]);
+    }
+
+    #[test]
+    fn discarded_not_flushed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+
+        add_pending_to(path.clone(), &file("123456abcdef"));
+        discard_pending(&file("123456abcdef"));
+        flush_pending();
+        assert!(!path.exists());
+    }
+}
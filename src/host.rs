@@ -1,4 +1,11 @@
-use ffsend_api::url::{ParseError, Url};
+use ffsend_api::crypto::b64;
+use ffsend_api::url::{Host, ParseError, Url};
+use idna::domain_to_unicode;
+
+use crate::util::print_warning;
+
+/// The marker separating the secret and owner token in the fragment of a management URL.
+const OWNER_TOKEN_MARKER: &str = "&owner=";
 
 /// Parse the given host string, into an URL.
 pub fn parse_host(host: &str) -> Result<Url, HostError> {
@@ -22,6 +29,174 @@ pub fn parse_host(host: &str) -> Result<Url, HostError> {
     })
 }
 
+/// Parse and normalize the given host string, into an URL to use as base for API requests.
+///
+/// The `https://` scheme is assumed if no scheme is given. Any query or fragment is dropped, and
+/// the path is normalized to end with a single slash, so API endpoints are joined onto it rather
+/// than replacing it's last segment.
+pub fn normalize_host(host: &str) -> Result<Url, HostError> {
+    // Assume HTTPS if no scheme is given
+    let host = host.trim();
+    let mut url = if host.contains("://") {
+        parse_host(host)?
+    } else {
+        parse_host(&format!("https://{}", host))?
+    };
+
+    // Normalize the path, drop the query and fragment
+    let path = format!("{}/", url.path().trim_end_matches('/'));
+    url.set_path(&path);
+    url.set_query(None);
+    url.set_fragment(None);
+
+    Ok(url)
+}
+
+/// Check whether the given host or share URL may be used, refusing plaintext `http://` URLs.
+///
+/// Plaintext URLs are only allowed if `allow_http` is set. Loopback hosts are always allowed for
+/// local testing.
+pub fn check_https(url: &Url, allow_http: bool) -> Result<(), HostError> {
+    if is_plaintext(url) && !allow_http {
+        return Err(HostError::Plaintext);
+    }
+    Ok(())
+}
+
+/// Check whether the given URL uses plaintext `http://` to a host that isn't a loopback address.
+pub fn is_plaintext(url: &Url) -> bool {
+    url.scheme() == "http" && !is_loopback(url)
+}
+
+/// Print a warning about the risk of using the given plaintext URL, if it is plaintext.
+pub fn warn_plaintext(url: &Url) {
+    if is_plaintext(url) {
+        print_warning(
+            "using a plaintext 'http://' host, the file and it's secret may be intercepted",
+        );
+    }
+}
+
+/// Check whether the host of the given URL is a loopback address.
+fn is_loopback(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Domain(domain)) => domain == "localhost",
+        Some(Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    }
+}
+
+/// Format the given URL for display.
+///
+/// Internationalized domain names are encoded as punycode when parsing an URL, which is the form
+/// used for requests. This shows such domain in it's original Unicode form instead.
+pub fn format_url(url: &Url) -> String {
+    // Only domains having a punycode label need to be converted
+    let domain = match url.domain() {
+        Some(domain) if domain.split('.').any(|l| l.starts_with("xn--")) => domain,
+        _ => return url.as_str().into(),
+    };
+
+    // Convert the domain, show the URL as-is if it can't be decoded
+    match domain_to_unicode(domain) {
+        (unicode, Ok(())) => url.as_str().replacen(domain, &unicode, 1),
+        (_, Err(_)) => url.as_str().into(),
+    }
+}
+
+/// Parse the given share URL or compact `host#id:secret` share code, into an URL.
+pub fn parse_share_url(url: &str) -> Result<Url, ShareUrlError> {
+    if is_share_code(url) {
+        parse_share_code(url).map_err(ShareUrlError::ShareCode)
+    } else {
+        parse_host(url).map_err(ShareUrlError::Url)
+    }
+}
+
+/// Build a management URL, for the given share URL and owner token.
+///
+/// A management URL is a share URL with the owner token appended to the fragment, formatted as
+/// `#<secret>&owner=<token>`. It allows managing the file straight from the URL. Regular share
+/// URLs never include the owner token, and a management URL must not be shared with others.
+pub fn management_url(url: &Url, owner_token: &str) -> Url {
+    let mut url = url.clone();
+    let fragment = format!(
+        "{}{}{}",
+        url.fragment().unwrap_or(""),
+        OWNER_TOKEN_MARKER,
+        owner_token,
+    );
+    url.set_fragment(Some(&fragment));
+    url
+}
+
+/// Take the owner token from the given management URL, turning it into a regular share URL.
+///
+/// `None` is returned if this is a regular share URL without an owner token.
+pub fn take_owner_token(url: &mut Url) -> Option<String> {
+    // Split the owner token from the secret in the fragment
+    let (secret, token) = {
+        let fragment = url.fragment()?;
+        let pos = fragment.find(OWNER_TOKEN_MARKER)?;
+        (
+            fragment[..pos].to_owned(),
+            fragment[pos + OWNER_TOKEN_MARKER.len()..].to_owned(),
+        )
+    };
+
+    url.set_fragment(Some(&secret));
+    if token.is_empty() {
+        None
+    } else {
+        Some(token)
+    }
+}
+
+/// Check whether the given string is a compact share code, formatted as `host#id:secret`.
+///
+/// Share codes never have an URL scheme, which distinguishes them from regular share URLs.
+pub fn is_share_code(code: &str) -> bool {
+    let code = code.trim();
+    !code.contains("://") && code.contains('#')
+}
+
+/// Expand the given compact share code, formatted as `host#id:secret`, into a full share URL.
+///
+/// The `https://` scheme is always used for the expanded URL.
+pub fn parse_share_code(code: &str) -> Result<Url, ShareCodeError> {
+    // Split the code into the host, file ID and secret
+    let mut parts = code.trim().splitn(2, '#');
+    let host = parts.next().unwrap_or("");
+    let mut parts = parts.next().ok_or(ShareCodeError::Format)?.splitn(2, ':');
+    let id = parts.next().unwrap_or("");
+    let secret = parts.next().ok_or(ShareCodeError::Format)?;
+
+    // Validate the file ID and secret
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ShareCodeError::Id);
+    }
+    if secret.is_empty() || b64::decode(secret).is_err() {
+        return Err(ShareCodeError::Secret);
+    }
+
+    // Build and parse the full share URL
+    parse_host(&format!("https://{}/download/{}/#{}", host, id, secret))
+        .map_err(ShareCodeError::Host)
+}
+
+/// An error that has occurred while parsing a share URL or share code.
+#[derive(Debug, Fail)]
+pub enum ShareUrlError {
+    /// The given share code is invalid.
+    #[fail(display = "failed to parse the given share code")]
+    ShareCode(#[cause] ShareCodeError),
+
+    /// The given share URL is invalid.
+    #[fail(display = "failed to parse the given share URL")]
+    Url(#[cause] HostError),
+}
+
 /// An error that has occurred while parsing a host.
 #[derive(Debug, Fail)]
 pub enum HostError {
@@ -53,7 +228,188 @@ pub enum HostError {
     #[fail(display = "missing host in the host URL")]
     NoBase,
 
+    /// The URL uses plaintext HTTP, which isn't allowed.
+    #[fail(
+        display = "refusing plaintext 'http://' host, the file and it's secret could be intercepted"
+    )]
+    Plaintext,
+
     /// Failed to parse the host URL due to another reason.
     #[fail(display = "could not parse host URL")]
     Other(#[cause] ParseError),
 }
+
+/// An error that has occurred while parsing a compact share code.
+#[derive(Debug, Fail)]
+pub enum ShareCodeError {
+    /// The share code is not formatted as `host#id:secret`.
+    #[fail(display = "the share code must be formatted as 'host#id:secret'")]
+    Format,
+
+    /// The file ID in the share code is invalid.
+    #[fail(display = "invalid file ID in the share code")]
+    Id,
+
+    /// The secret in the share code is invalid.
+    #[fail(display = "invalid secret in the share code")]
+    Secret,
+
+    /// The host in the share code is invalid.
+    #[fail(display = "invalid host in the share code")]
+    Host(#[cause] HostError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn check_https_secure() {
+        assert!(check_https(&url("https://send.example.com/"), false).is_ok());
+    }
+
+    #[test]
+    fn check_https_plaintext() {
+        match check_https(&url("http://send.example.com/download/abc/#key"), false) {
+            Err(HostError::Plaintext) => {}
+            _ => panic!("expected a plaintext error"),
+        }
+        assert!(check_https(&url("http://send.example.com/"), true).is_ok());
+    }
+
+    #[test]
+    fn check_https_loopback() {
+        for host in &[
+            "http://localhost:1443/",
+            "http://127.0.0.1/",
+            "http://[::1]/",
+        ] {
+            assert!(check_https(&url(host), false).is_ok(), "{}", host);
+        }
+    }
+
+    #[test]
+    fn share_code_detect() {
+        assert!(is_share_code("send.example.com#abc:c2VjcmV0"));
+        assert!(is_share_code(" send.example.com#abc:c2VjcmV0 "));
+        assert!(!is_share_code(
+            "https://send.example.com/download/abc/#c2VjcmV0"
+        ));
+        assert!(!is_share_code("send.example.com"));
+    }
+
+    #[test]
+    fn share_code_parse() {
+        assert_eq!(
+            parse_share_code("send.example.com#abc123:c2VjcmV0")
+                .unwrap()
+                .as_str(),
+            "https://send.example.com/download/abc123/#c2VjcmV0",
+        );
+        assert_eq!(
+            parse_share_code(" localhost:1443#abc:c2VjcmV0 ")
+                .unwrap()
+                .as_str(),
+            "https://localhost:1443/download/abc/#c2VjcmV0",
+        );
+    }
+
+    #[test]
+    fn share_code_invalid() {
+        match parse_share_code("send.example.com#abc") {
+            Err(ShareCodeError::Format) => {}
+            _ => panic!("expected a format error"),
+        }
+        for code in &[
+            "send.example.com#:c2VjcmV0",
+            "send.example.com#a/b:c2VjcmV0",
+        ] {
+            match parse_share_code(code) {
+                Err(ShareCodeError::Id) => {}
+                _ => panic!("expected an ID error for: {}", code),
+            }
+        }
+        for code in &["send.example.com#abc:", "send.example.com#abc:!!"] {
+            match parse_share_code(code) {
+                Err(ShareCodeError::Secret) => {}
+                _ => panic!("expected a secret error for: {}", code),
+            }
+        }
+        match parse_share_code("exa mple.com#abc:c2VjcmV0") {
+            Err(ShareCodeError::Host(_)) => {}
+            _ => panic!("expected a host error"),
+        }
+    }
+
+    #[test]
+    fn owner_token_round_trip() {
+        let share = url("https://send.example.com/download/abc/#c2VjcmV0");
+        let mut manage = management_url(&share, "token123");
+        assert_eq!(
+            manage.as_str(),
+            "https://send.example.com/download/abc/#c2VjcmV0&owner=token123",
+        );
+        assert_eq!(take_owner_token(&mut manage), Some("token123".into()));
+        assert_eq!(manage, share);
+    }
+
+    #[test]
+    fn owner_token_absent() {
+        let mut share = url("https://send.example.com/download/abc/#c2VjcmV0");
+        assert_eq!(take_owner_token(&mut share), None);
+        assert_eq!(
+            share.as_str(),
+            "https://send.example.com/download/abc/#c2VjcmV0"
+        );
+
+        let mut share = url("https://send.example.com/download/abc/");
+        assert_eq!(take_owner_token(&mut share), None);
+    }
+
+    #[test]
+    fn owner_token_empty() {
+        let mut manage = url("https://send.example.com/download/abc/#c2VjcmV0&owner=");
+        assert_eq!(take_owner_token(&mut manage), None);
+        assert_eq!(
+            manage.as_str(),
+            "https://send.example.com/download/abc/#c2VjcmV0"
+        );
+    }
+
+    #[test]
+    fn normalize_host_scheme() {
+        assert_eq!(
+            normalize_host("send.example.com").unwrap().as_str(),
+            "https://send.example.com/",
+        );
+        assert_eq!(
+            normalize_host(" http://localhost:1443 ").unwrap().as_str(),
+            "http://localhost:1443/",
+        );
+        match normalize_host("ftp://send.example.com") {
+            Err(HostError::Scheme) => {}
+            _ => panic!("expected a scheme error"),
+        }
+    }
+
+    #[test]
+    fn normalize_host_path() {
+        for host in &[
+            "https://example.com/send",
+            "https://example.com/send/",
+            "https://example.com/send//",
+            "https://example.com/send/?query=1#fragment",
+        ] {
+            assert_eq!(
+                normalize_host(host).unwrap().as_str(),
+                "https://example.com/send/",
+                "{}",
+                host,
+            );
+        }
+    }
+}
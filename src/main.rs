@@ -6,7 +6,8 @@ extern crate derive_builder;
 extern crate failure;
 #[macro_use]
 extern crate lazy_static;
-#[cfg(feature = "history")]
+#[macro_use]
+extern crate log;
 #[macro_use]
 extern crate serde_derive;
 
@@ -16,17 +17,25 @@ mod archive;
 mod client;
 mod cmd;
 mod config;
+mod config_file;
 mod error;
 #[cfg(feature = "history")]
 mod history;
 #[cfg(feature = "history")]
 mod history_tool;
 mod host;
+mod logging;
+mod metrics;
 mod progress;
+#[cfg(feature = "history")]
+mod secret_store;
+mod split;
 #[cfg(feature = "urlshorten")]
 mod urlshorten;
 mod util;
+mod webhook;
 
+use std::path::Path;
 use std::process;
 
 use crate::action::debug::Debug;
@@ -46,7 +55,10 @@ use crate::cmd::{
     Handler,
 };
 use crate::error::Error;
-use crate::util::{bin_name, highlight, quit_error, ErrorHints};
+use crate::util::{
+    bin_name, highlight, quit_error, quit_error_msg, set_json_output, set_si_units, ErrorHints,
+    ErrorHintsBuilder,
+};
 
 /// Application entrypoint.
 fn main() {
@@ -56,8 +68,34 @@ fn main() {
     // Parse CLI arguments
     let cmd_handler = Handler::parse();
 
+    // Select the units to format sizes in, and whether to produce JSON output
+    let matcher_main = MainMatcher::with(cmd_handler.matches()).unwrap();
+    set_si_units(matcher_main.si());
+    set_json_output(matcher_main.json());
+    if !matcher_main.json() {
+        logging::init(matcher_main.verbosity());
+    }
+
+    // Add uploaded files to the history when interrupted
+    #[cfg(feature = "history")]
+    history_tool::handle_signals();
+
+    // Write the transfer metrics of this run when quitting, not mixed into JSON output on stdout
+    if let Some(path) = matcher_main.metrics_file() {
+        if matcher_main.json() && path == Path::new("-") {
+            quit_error_msg(
+                "writing metrics to stdout can't be combined with JSON output",
+                ErrorHintsBuilder::default().verbose(false).build().unwrap(),
+            );
+        }
+        metrics::set_output(path, matcher_main.metrics_format());
+    }
+
     // Invoke the proper action
-    if let Err(err) = invoke_action(&cmd_handler) {
+    let result = invoke_action(&cmd_handler);
+    metrics::flush();
+
+    if let Err(err) = result {
         quit_error(err, ErrorHints::default());
     };
 }
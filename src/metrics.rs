@@ -0,0 +1,245 @@
+//! Accounting of transferred bytes, written as metrics through `--metrics-file`.
+//!
+//! Transfers are recorded as they finish, and accumulate over a whole run, such as a batch of
+//! downloads with `download --from-file`. The metrics are written once the run finishes, also
+//! when it quits early with an error.
+
+use std::fs;
+use std::io::{self, Error as IoError, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use failure::Fail;
+
+use crate::util::print_error;
+
+lazy_static! {
+    /// The metrics accumulated during this run.
+    static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics::default());
+
+    /// The file and format to write the metrics of this run in, taken once written.
+    static ref OUTPUT: Mutex<Option<(PathBuf, Format)>> = Mutex::new(None);
+}
+
+/// The direction of a transfer.
+#[derive(Clone, Copy, Debug)]
+pub enum Direction {
+    /// An upload.
+    Upload,
+
+    /// A download.
+    Download,
+}
+
+/// The format to write metrics in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// A single JSON object.
+    Json,
+
+    /// The Prometheus text exposition format.
+    Prometheus,
+}
+
+impl Format {
+    /// Parse the format from the given name, `json` or `prometheus`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "prometheus" => Some(Format::Prometheus),
+            _ => None,
+        }
+    }
+}
+
+/// Transfer metrics, accumulated over all transfers of a run.
+#[derive(Debug, Default, PartialEq)]
+struct Metrics {
+    /// The number of uploaded bytes.
+    uploaded_bytes: u64,
+
+    /// The number of downloaded bytes.
+    downloaded_bytes: u64,
+
+    /// The time spent transferring.
+    duration: Duration,
+
+    /// The number of successful transfers.
+    succeeded: usize,
+
+    /// The number of failed transfers.
+    failed: usize,
+}
+
+/// Transfer metrics, as written in the JSON format.
+#[derive(Serialize)]
+struct JsonMetrics {
+    uploaded_bytes: u64,
+    downloaded_bytes: u64,
+    duration_seconds: f64,
+    succeeded: usize,
+    failed: usize,
+}
+
+impl Metrics {
+    /// Record a successful transfer of `bytes` that took `elapsed`.
+    fn record(&mut self, direction: Direction, bytes: u64, elapsed: Duration) {
+        match direction {
+            Direction::Upload => self.uploaded_bytes += bytes,
+            Direction::Download => self.downloaded_bytes += bytes,
+        }
+        self.duration += elapsed;
+        self.succeeded += 1;
+    }
+
+    /// The time spent transferring, in seconds.
+    fn duration_seconds(&self) -> f64 {
+        self.duration.as_millis() as f64 / 1000.0
+    }
+
+    /// Format the metrics in the given format.
+    fn format(&self, format: Format) -> String {
+        match format {
+            Format::Json => {
+                let metrics = JsonMetrics {
+                    uploaded_bytes: self.uploaded_bytes,
+                    downloaded_bytes: self.downloaded_bytes,
+                    duration_seconds: self.duration_seconds(),
+                    succeeded: self.succeeded,
+                    failed: self.failed,
+                };
+                format!("{}\n", serde_json::to_string(&metrics).unwrap())
+            }
+            Format::Prometheus => format!(
+                "# HELP ffsend_transferred_bytes_total Number of transferred bytes.\n\
+                 # TYPE ffsend_transferred_bytes_total counter\n\
+                 ffsend_transferred_bytes_total{{direction=\"upload\"}} {}\n\
+                 ffsend_transferred_bytes_total{{direction=\"download\"}} {}\n\
+                 # HELP ffsend_transfer_duration_seconds_total Time spent transferring.\n\
+                 # TYPE ffsend_transfer_duration_seconds_total counter\n\
+                 ffsend_transfer_duration_seconds_total {}\n\
+                 # HELP ffsend_transfers_total Number of transfers, by result.\n\
+                 # TYPE ffsend_transfers_total counter\n\
+                 ffsend_transfers_total{{result=\"success\"}} {}\n\
+                 ffsend_transfers_total{{result=\"failure\"}} {}\n",
+                self.uploaded_bytes,
+                self.downloaded_bytes,
+                self.duration_seconds(),
+                self.succeeded,
+                self.failed,
+            ),
+        }
+    }
+}
+
+/// Record a successful transfer of `bytes` in the given direction, that took `elapsed`.
+pub fn record(direction: Direction, bytes: u64, elapsed: Duration) {
+    METRICS.lock().unwrap().record(direction, bytes, elapsed);
+}
+
+/// Record the given number of failed transfers.
+pub fn record_failures(count: usize) {
+    METRICS.lock().unwrap().failed += count;
+}
+
+/// Set the file to write the metrics of this run to when it finishes, `-` for stdout.
+pub fn set_output(path: PathBuf, format: Format) {
+    *OUTPUT.lock().unwrap() = Some((path, format));
+}
+
+/// Write the metrics of this run to the file set with `set_output`, if any.
+///
+/// The metrics are written only once, so this is called on every path that quits. Errors are
+/// printed and ignored.
+pub fn flush() {
+    let output = OUTPUT.lock().unwrap().take();
+    if let Some((path, format)) = output {
+        if let Err(err) = write(&path, format) {
+            print_error(err.context("failed to write metrics file"));
+        }
+    }
+}
+
+/// Write the metrics of this run to the file at the given path, or to stdout if it is `-`.
+///
+/// An existing file is replaced.
+pub fn write(path: &Path, format: Format) -> Result<(), IoError> {
+    let data = METRICS.lock().unwrap().format(format);
+    if path == Path::new("-") {
+        io::stdout().write_all(data.as_bytes())
+    } else {
+        fs::write(path, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Metrics for a batch of two downloads and an upload, one of the downloads failed.
+    fn metrics() -> Metrics {
+        let mut metrics = Metrics::default();
+        metrics.record(Direction::Download, 1000, Duration::from_millis(1500));
+        metrics.record(Direction::Download, 24, Duration::from_millis(250));
+        metrics.record(Direction::Upload, 2048, Duration::from_secs(2));
+        metrics.failed += 1;
+        metrics
+    }
+
+    #[test]
+    fn record_accumulates() {
+        let metrics = metrics();
+        assert_eq!(metrics.uploaded_bytes, 2048);
+        assert_eq!(metrics.downloaded_bytes, 1024);
+        assert_eq!(metrics.duration, Duration::from_millis(3750));
+        assert_eq!(metrics.succeeded, 3);
+        assert_eq!(metrics.failed, 1);
+    }
+
+    #[test]
+    fn format_json() {
+        assert_eq!(
+            metrics().format(Format::Json),
+            "{\"uploaded_bytes\":2048,\"downloaded_bytes\":1024,\"duration_seconds\":3.75,\
+             \"succeeded\":3,\"failed\":1}\n",
+        );
+    }
+
+    #[test]
+    fn format_prometheus() {
+        let data = metrics().format(Format::Prometheus);
+        let samples: Vec<&str> = data.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(
+            samples,
+            vec![
+                "ffsend_transferred_bytes_total{direction=\"upload\"} 2048",
+                "ffsend_transferred_bytes_total{direction=\"download\"} 1024",
+                "ffsend_transfer_duration_seconds_total 3.75",
+                "ffsend_transfers_total{result=\"success\"} 3",
+                "ffsend_transfers_total{result=\"failure\"} 1",
+            ],
+        );
+    }
+
+    #[test]
+    fn format_parse() {
+        assert_eq!(Format::parse("json"), Some(Format::Json));
+        assert_eq!(Format::parse("Prometheus"), Some(Format::Prometheus));
+        assert_eq!(Format::parse("csv"), None);
+    }
+
+    #[test]
+    fn flush_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.json");
+        set_output(path.clone(), Format::Json);
+
+        flush();
+        assert!(path.is_file());
+
+        fs::remove_file(&path).unwrap();
+        flush();
+        assert!(!path.exists());
+    }
+}
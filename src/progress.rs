@@ -1,19 +1,63 @@
+#[cfg(unix)]
+extern crate libc;
 extern crate pbr;
+extern crate terminal_size;
 
-use std::io::{stderr, Stderr};
-use std::time::Duration;
+use std::fs::OpenOptions;
+use std::io::{stderr, Error as IoError, ErrorKind, Stderr, Write};
+#[cfg(unix)]
+use std::os::unix::{
+    fs::{FileTypeExt, OpenOptionsExt},
+    io::AsRawFd,
+    net::UnixStream,
+};
+use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, RecvTimeoutError},
+    Arc, Mutex,
+};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use self::pbr::{ProgressBar as Pbr, Units};
+use self::terminal_size::Width;
+use atty::Stream;
+use failure::Fail;
 use ffsend_api::pipe::ProgressReporter;
 
+use crate::cmd::matcher::MainMatcher;
+use crate::util::{format_bytes, print_error};
+
 /// The refresh rate of the progress bar, in milliseconds.
 const PROGRESS_BAR_FPS_MILLIS: u64 = 200;
 
+/// The interval to check whether a watched transfer stalled, in milliseconds.
+pub const STALL_POLL_MILLIS: u64 = 200;
+
+/// The interval of progress lines when stderr isn't a terminal, in seconds.
+const PROGRESS_LOG_INTERVAL: u64 = 5;
+
+/// The terminal width below which the progress message is dropped.
+const WIDTH_NO_MESSAGE: u16 = 80;
+
+/// The terminal width below which the time left and speed are dropped.
+const WIDTH_NO_STATS: u16 = 60;
+
+/// The terminal width below which only the percentage is shown.
+const WIDTH_MINIMAL: u16 = 40;
+
 /// A progress bar reporter.
+///
+/// If stderr isn't a terminal, such as in CI logs, a progress line is printed periodically
+/// instead of drawing a bar.
 pub struct ProgressBar<'a> {
     progress_bar: Option<Pbr<Stderr>>,
     msg_progress: &'a str,
     msg_finish: &'a str,
+
+    /// The total and time of the last line, when reporting as periodic lines.
+    log: Option<(u64, Instant)>,
 }
 
 impl<'a> ProgressBar<'a> {
@@ -23,6 +67,7 @@ impl<'a> ProgressBar<'a> {
             progress_bar: None,
             msg_progress,
             msg_finish,
+            log: None,
         }
     }
 
@@ -37,20 +82,93 @@ impl<'a> ProgressBar<'a> {
     }
 }
 
+/// The fields to show in a progress bar.
+#[derive(Debug, PartialEq)]
+struct Fields {
+    /// Whether to show the message.
+    message: bool,
+
+    /// Whether to show the speed and time left.
+    stats: bool,
+
+    /// Whether to show the bar and counter.
+    bar: bool,
+}
+
+impl Fields {
+    /// Select the fields that fit a terminal of the given `width`.
+    fn for_width(width: u16) -> Self {
+        Fields {
+            message: width >= WIDTH_NO_MESSAGE,
+            stats: width >= WIDTH_NO_STATS,
+            bar: width >= WIDTH_MINIMAL,
+        }
+    }
+}
+
+/// Get the width of the terminal standard error is attached to, which the progress bar is drawn
+/// on.
+///
+/// `None` is returned if standard error isn't a terminal.
+fn stderr_width() -> Option<u16> {
+    #[cfg(unix)]
+    let size = terminal_size::terminal_size_using_fd(stderr().as_raw_fd());
+    #[cfg(windows)]
+    let size = {
+        use std::os::windows::io::AsRawHandle;
+        terminal_size::terminal_size_using_handle(stderr().as_raw_handle())
+    };
+    #[cfg(not(any(unix, windows)))]
+    let size = terminal_size::terminal_size();
+
+    size.map(|(Width(width), _)| width)
+}
+
 impl<'a> ProgressReporter for ProgressBar<'a> {
     /// Start the progress with the given total.
     fn start(&mut self, total: u64) {
+        // Report periodic lines if not drawing to a terminal
+        if atty::isnt(Stream::Stderr) {
+            eprintln!("{}{}", self.msg_progress, format_bytes(total));
+            self.log = Some((total, Instant::now()));
+            return;
+        }
+
         // Initialize the progress bar
         let mut progress_bar = Pbr::on(stderr(), total);
         progress_bar.set_max_refresh_rate(Some(Duration::from_millis(PROGRESS_BAR_FPS_MILLIS)));
         progress_bar.set_units(Units::Bytes);
         progress_bar.message(self.msg_progress);
 
+        // Drop fields from the progress bar to fit narrow terminals
+        if let Some(width) = stderr_width() {
+            let fields = Fields::for_width(width);
+            progress_bar.show_message = fields.message;
+            progress_bar.show_time_left = fields.stats;
+            progress_bar.show_speed = fields.stats;
+            progress_bar.show_bar = fields.bar;
+            progress_bar.show_counter = fields.bar;
+        }
+
         self.progress_bar = Some(progress_bar);
     }
 
     /// A progress update.
     fn progress(&mut self, progress: u64) {
+        if let Some((total, last)) = self.log.as_mut() {
+            if last.elapsed() >= Duration::from_secs(PROGRESS_LOG_INTERVAL) {
+                *last = Instant::now();
+                eprintln!(
+                    "{}{} / {} ({}%)",
+                    self.msg_progress,
+                    format_bytes(progress),
+                    format_bytes(*total),
+                    (progress * 100).checked_div(*total).unwrap_or(100),
+                );
+            }
+            return;
+        }
+
         self.progress_bar
             .as_mut()
             .expect("progress bar not yet instantiated, cannot set progress")
@@ -59,9 +177,674 @@ impl<'a> ProgressReporter for ProgressBar<'a> {
 
     /// Finish the progress.
     fn finish(&mut self) {
+        if self.log.is_some() {
+            eprintln!("{}", self.msg_finish);
+            return;
+        }
+
         self.progress_bar
             .as_mut()
             .expect("progress bar not yet instantiated")
             .finish_print(self.msg_finish);
     }
 }
+
+/// A progress reporter writing progress events to a socket or named pipe.
+///
+/// Each event is written as single line of JSON, such as `{"event":"progress","progress":512}`,
+/// so that another process can display the progress. An inner reporter, such as a progress bar,
+/// may be wrapped to report to both.
+///
+/// If writing fails, because the other side disconnected for example, reporting to the socket is
+/// stopped while the transfer continues.
+pub struct ProgressSocket<R: ProgressReporter> {
+    inner: Option<R>,
+    writer: Option<Box<dyn Write + Send>>,
+}
+
+impl<R: ProgressReporter> ProgressSocket<R> {
+    /// Construct a new socket reporter, writing to the given writer.
+    pub fn new(inner: Option<R>, writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            inner,
+            writer: Some(writer),
+        }
+    }
+
+    /// Write the given JSON event line, stop reporting if writing failed.
+    fn write_event(&mut self, event: &str) {
+        let failed = match self.writer.as_mut() {
+            Some(writer) => writeln!(writer, "{}", event)
+                .and_then(|_| writer.flush())
+                .is_err(),
+            None => false,
+        };
+        if failed {
+            self.writer = None;
+        }
+    }
+}
+
+impl<R: ProgressReporter> ProgressReporter for ProgressSocket<R> {
+    /// Start the progress with the given total.
+    fn start(&mut self, total: u64) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.start(total);
+        }
+        self.write_event(&format!("{{\"event\":\"start\",\"total\":{}}}", total));
+    }
+
+    /// A progress update.
+    fn progress(&mut self, progress: u64) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.progress(progress);
+        }
+        self.write_event(&format!(
+            "{{\"event\":\"progress\",\"progress\":{}}}",
+            progress
+        ));
+    }
+
+    /// Finish the progress.
+    fn finish(&mut self) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.finish();
+        }
+        self.write_event("{\"event\":\"finish\"}");
+    }
+}
+
+/// A signal raised by a `StallDetector` when the transfer it watches stalls.
+///
+/// The transfer itself is run through `watch`, which stops waiting for it once this is raised.
+#[derive(Clone, Default)]
+pub struct StallSignal(Arc<AtomicBool>);
+
+impl StallSignal {
+    /// Raise the signal, the transfer stalled or must be stopped.
+    pub fn raise(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Check whether the signal was raised.
+    pub fn is_raised(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// A source of the current time.
+pub trait Clock: Send + Sync {
+    /// Get the current time.
+    fn now(&self) -> Instant;
+}
+
+/// The system clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A progress reporter detecting stalled transfers.
+///
+/// When the transfer starts, a watchdog thread is spawned that raises the stall signal if no
+/// progress has been reported within the stall timeout. This catches connections that stop
+/// transferring data without erroring, which the transfer timeout may not catch for a long time.
+pub struct StallDetector {
+    inner: Option<Arc<Mutex<dyn ProgressReporter>>>,
+    progress: u64,
+    watchdog: Watchdog,
+
+    /// The interval the watchdog checks for stalls at, `None` to not spawn a watchdog thread.
+    poll: Option<Duration>,
+}
+
+impl StallDetector {
+    /// Construct a new stall detector, wrapping the given inner reporter.
+    ///
+    /// The given `signal` is raised when the transfer stalls.
+    pub fn new(
+        inner: Option<Arc<Mutex<dyn ProgressReporter>>>,
+        timeout: Duration,
+        signal: StallSignal,
+    ) -> Self {
+        Self::with_clock(
+            inner,
+            timeout,
+            signal,
+            Arc::new(SystemClock),
+            Some(Duration::from_millis(STALL_POLL_MILLIS)),
+        )
+    }
+
+    /// Construct a new stall detector, using the given clock and watchdog poll interval.
+    ///
+    /// See `new`.
+    fn with_clock(
+        inner: Option<Arc<Mutex<dyn ProgressReporter>>>,
+        timeout: Duration,
+        signal: StallSignal,
+        clock: Arc<dyn Clock>,
+        poll: Option<Duration>,
+    ) -> Self {
+        Self {
+            inner,
+            progress: 0,
+            watchdog: Watchdog {
+                timeout,
+                signal,
+                last_progress: Arc::new(Mutex::new(clock.now())),
+                done: Arc::new(AtomicBool::new(true)),
+                clock,
+            },
+            poll,
+        }
+    }
+}
+
+/// The watchdog of a `StallDetector`, checking whether the transfer stalled.
+#[derive(Clone)]
+struct Watchdog {
+    timeout: Duration,
+    signal: StallSignal,
+    last_progress: Arc<Mutex<Instant>>,
+    done: Arc<AtomicBool>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Watchdog {
+    /// Mark progress as being made right now.
+    fn touch(&self) {
+        *self.last_progress.lock().unwrap() = self.clock.now();
+    }
+
+    /// Check whether the transfer stalled, and raise the signal if it did.
+    ///
+    /// True is returned once watching can stop, because the transfer finished or stalled.
+    fn check(&self) -> bool {
+        if self.done.load(Ordering::SeqCst) {
+            return true;
+        }
+        let now = self.clock.now();
+        let last_progress = *self.last_progress.lock().unwrap();
+        if now >= last_progress && now - last_progress >= self.timeout {
+            self.signal.raise();
+            return true;
+        }
+        false
+    }
+}
+
+impl ProgressReporter for StallDetector {
+    /// Start the progress with the given total.
+    fn start(&mut self, total: u64) {
+        if let Some(inner) = self.inner.as_ref() {
+            inner.lock().unwrap().start(total);
+        }
+
+        // Stop any previous watchdog, and reset the progress state
+        self.watchdog.done.store(true, Ordering::SeqCst);
+        self.watchdog.done = Arc::new(AtomicBool::new(false));
+        self.progress = 0;
+        self.watchdog.touch();
+
+        // Spawn the watchdog, raise the signal when no progress is made within the timeout
+        if let Some(poll) = self.poll {
+            let watchdog = self.watchdog.clone();
+            thread::spawn(move || loop {
+                thread::sleep(poll);
+                if watchdog.check() {
+                    return;
+                }
+            });
+        }
+    }
+
+    /// A progress update.
+    fn progress(&mut self, progress: u64) {
+        if let Some(inner) = self.inner.as_ref() {
+            inner.lock().unwrap().progress(progress);
+        }
+        if progress > self.progress {
+            self.progress = progress;
+            self.watchdog.touch();
+        }
+    }
+
+    /// Finish the progress.
+    fn finish(&mut self) {
+        self.watchdog.done.store(true, Ordering::SeqCst);
+        if let Some(inner) = self.inner.as_ref() {
+            inner.lock().unwrap().finish();
+        }
+    }
+}
+
+impl Drop for StallDetector {
+    fn drop(&mut self) {
+        // Stop the watchdog if the transfer failed before finishing
+        self.watchdog.done.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Run the given transfer on a separate thread, and wait for it unless it stalls.
+///
+/// The result of the transfer is returned when it finishes. If the given `signal` is raised
+/// first, `WatchError::Stalled` is returned right away. A stalled transfer can't be cancelled, it
+/// is abandoned on it's thread instead, which is why the transfer must own all it's data.
+pub fn watch<T, E, F>(signal: &StallSignal, transfer: F) -> Result<T, E>
+where
+    T: Send + 'static,
+    E: From<WatchError> + Send + 'static,
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(transfer());
+    });
+
+    loop {
+        match receiver.recv_timeout(Duration::from_millis(STALL_POLL_MILLIS)) {
+            Ok(result) => return result,
+            Err(RecvTimeoutError::Timeout) if signal.is_raised() => {
+                return Err(WatchError::Stalled.into())
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Err(WatchError::Panicked.into()),
+        }
+    }
+}
+
+/// A watched transfer didn't finish.
+#[derive(Debug, Fail)]
+pub enum WatchError {
+    /// The transfer stalled, no progress was made within the stall timeout.
+    #[fail(display = "transfer stalled, no progress made within the stall timeout")]
+    Stalled,
+
+    /// The transfer ended without a result, it's thread panicked.
+    #[fail(display = "transfer ended unexpectedly")]
+    Panicked,
+}
+
+/// Open the socket or named pipe at the given path to write progress events to.
+///
+/// On Unix, a Unix domain socket is connected to. Any other path, such as a named pipe, is opened
+/// for writing. Opening a named pipe fails if it has no reader, rather than blocking until one
+/// shows up.
+fn open_socket(path: &Path) -> Result<Box<dyn Write + Send>, IoError> {
+    #[cfg(unix)]
+    {
+        let file_type = path.metadata()?.file_type();
+        if file_type.is_socket() {
+            return Ok(Box::new(UnixStream::connect(path)?));
+        }
+        if file_type.is_fifo() {
+            return open_fifo(path).map(|fifo| Box::new(fifo) as Box<dyn Write + Send>);
+        }
+    }
+
+    Ok(Box::new(OpenOptions::new().write(true).open(path)?))
+}
+
+/// Open the named pipe at the given path for writing, failing if it has no reader.
+///
+/// The pipe is opened non-blocking to not wait for a reader, and is made blocking again after
+/// opening so writes behave as usual.
+#[cfg(unix)]
+fn open_fifo(path: &Path) -> Result<std::fs::File, IoError> {
+    let fifo = OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+        .map_err(|err| match err.raw_os_error() {
+            Some(libc::ENXIO) => IoError::new(ErrorKind::NotConnected, "named pipe has no reader"),
+            _ => err,
+        })?;
+
+    let fd = fifo.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0 {
+        return Err(IoError::last_os_error());
+    }
+    Ok(fifo)
+}
+
+/// Build the progress reporter to use for a transfer.
+///
+/// The given progress bar is used unless in quiet mode, or if progress output is disabled. If a
+/// progress socket is configured, progress is also reported to it. If a stall timeout is
+/// configured, the reporter is wrapped in a `StallDetector`, raising the given `stall` signal.
+/// `None` is returned if progress shouldn't be reported at all.
+pub fn reporter(
+    progress_bar: ProgressBar<'static>,
+    matcher_main: &MainMatcher,
+    stall: &StallSignal,
+) -> Option<Arc<Mutex<dyn ProgressReporter>>> {
+    let progress_bar = if !matcher_main.quiet() && !matcher_main.no_progress() {
+        Some(progress_bar)
+    } else {
+        None
+    };
+
+    // Open the progress socket if configured, ignore it if it can't be opened
+    let socket = matcher_main
+        .progress_socket()
+        .and_then(|path| match open_socket(&path) {
+            Ok(socket) => Some(socket),
+            Err(err) => {
+                print_error(err.context("failed to open progress socket, ignoring"));
+                None
+            }
+        });
+
+    let reporter: Option<Arc<Mutex<dyn ProgressReporter>>> = match socket {
+        Some(socket) => Some(Arc::new(Mutex::new(ProgressSocket::new(
+            progress_bar,
+            socket,
+        )))),
+        None => {
+            progress_bar.map(|bar| Arc::new(Mutex::new(bar)) as Arc<Mutex<dyn ProgressReporter>>)
+        }
+    };
+
+    // Watch for stalled transfers if configured
+    match matcher_main.stall_timeout() {
+        Some(timeout) => Some(Arc::new(Mutex::new(StallDetector::new(
+            reporter,
+            Duration::from_secs(timeout),
+            stall.clone(),
+        )))),
+        None => reporter,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Fail)]
+    enum TransferError {
+        #[fail(display = "watch error")]
+        Watch(#[cause] WatchError),
+    }
+
+    impl From<WatchError> for TransferError {
+        fn from(err: WatchError) -> Self {
+            TransferError::Watch(err)
+        }
+    }
+
+    /// A clock that only moves when advanced manually.
+    struct MockClock {
+        start: Instant,
+        elapsed: Mutex<Duration>,
+    }
+
+    impl MockClock {
+        fn new() -> Arc<Self> {
+            Arc::new(MockClock {
+                start: Instant::now(),
+                elapsed: Mutex::new(Duration::from_secs(0)),
+            })
+        }
+
+        fn advance(&self, secs: u64) {
+            *self.elapsed.lock().unwrap() += Duration::from_secs(secs);
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            self.start + *self.elapsed.lock().unwrap()
+        }
+    }
+
+    /// Build a stall detector with the given timeout, checked manually through a mock clock.
+    fn detector(timeout: u64, signal: &StallSignal, clock: &Arc<MockClock>) -> StallDetector {
+        StallDetector::with_clock(
+            None,
+            Duration::from_secs(timeout),
+            signal.clone(),
+            clock.clone(),
+            None,
+        )
+    }
+
+    #[test]
+    fn watch_finished() {
+        let signal = StallSignal::default();
+        let result: Result<u32, TransferError> = watch(&signal, || Ok(42));
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[test]
+    fn watch_stalled() {
+        let signal = StallSignal::default();
+        signal.raise();
+
+        // The transfer blocks until the test ends, the stall must be reported instead
+        let (sender, receiver) = mpsc::channel::<()>();
+        let result: Result<(), TransferError> = watch(&signal, move || {
+            let _ = receiver.recv();
+            Ok(())
+        });
+        match result {
+            Err(TransferError::Watch(WatchError::Stalled)) => {}
+            _ => panic!("expected a stalled transfer"),
+        }
+
+        // Let the abandoned transfer end
+        drop(sender);
+    }
+
+    #[test]
+    fn watch_panicked() {
+        let signal = StallSignal::default();
+        let result: Result<(), TransferError> = watch(&signal, || panic!("transfer failed"));
+        match result {
+            Err(TransferError::Watch(WatchError::Panicked)) => {}
+            _ => panic!("expected a panicked transfer"),
+        }
+    }
+
+    #[test]
+    fn stall_detector_stalled() {
+        let (signal, clock) = (StallSignal::default(), MockClock::new());
+        let mut detector = detector(2, &signal, &clock);
+        detector.start(100);
+        clock.advance(1);
+        assert!(!detector.watchdog.check());
+        clock.advance(1);
+        assert!(detector.watchdog.check());
+        assert!(signal.is_raised());
+    }
+
+    #[test]
+    fn stall_detector_progress() {
+        let (signal, clock) = (StallSignal::default(), MockClock::new());
+        let mut detector = detector(2, &signal, &clock);
+        detector.start(100);
+        for i in 1..=4 {
+            clock.advance(1);
+            detector.progress(i);
+            assert!(!detector.watchdog.check());
+        }
+
+        // Repeated progress without advancing isn't progress
+        clock.advance(1);
+        detector.progress(4);
+        clock.advance(1);
+        assert!(detector.watchdog.check());
+        assert!(signal.is_raised());
+    }
+
+    #[test]
+    fn stall_detector_finished() {
+        let (signal, clock) = (StallSignal::default(), MockClock::new());
+        let mut detector = detector(2, &signal, &clock);
+        detector.start(100);
+        detector.finish();
+        clock.advance(10);
+        assert!(detector.watchdog.check());
+        assert!(!signal.is_raised());
+    }
+
+    #[test]
+    fn fields_for_width() {
+        let all = Fields {
+            message: true,
+            stats: true,
+            bar: true,
+        };
+        assert_eq!(Fields::for_width(120), all);
+        assert_eq!(Fields::for_width(WIDTH_NO_MESSAGE), all);
+        assert_eq!(
+            Fields::for_width(WIDTH_NO_MESSAGE - 1),
+            Fields {
+                message: false,
+                stats: true,
+                bar: true,
+            },
+        );
+        assert_eq!(
+            Fields::for_width(WIDTH_NO_STATS - 1),
+            Fields {
+                message: false,
+                stats: false,
+                bar: true,
+            },
+        );
+        assert_eq!(
+            Fields::for_width(WIDTH_MINIMAL - 1),
+            Fields {
+                message: false,
+                stats: false,
+                bar: false,
+            },
+        );
+        assert_eq!(Fields::for_width(0), Fields::for_width(WIDTH_MINIMAL - 1));
+    }
+
+    /// A writer collecting everything written to it in a shared buffer.
+    #[derive(Clone, Default)]
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> Result<(), IoError> {
+            Ok(())
+        }
+    }
+
+    /// A writer that always fails, like a socket of which the other side disconnected.
+    struct BrokenWriter;
+
+    impl Write for BrokenWriter {
+        fn write(&mut self, _: &[u8]) -> Result<usize, IoError> {
+            Err(IoError::new(std::io::ErrorKind::BrokenPipe, "disconnected"))
+        }
+
+        fn flush(&mut self) -> Result<(), IoError> {
+            Ok(())
+        }
+    }
+
+    /// A reporter recording the progress it receives.
+    #[derive(Default)]
+    struct Recorder(Vec<u64>);
+
+    impl ProgressReporter for Recorder {
+        fn start(&mut self, total: u64) {
+            self.0.push(total);
+        }
+
+        fn progress(&mut self, progress: u64) {
+            self.0.push(progress);
+        }
+
+        fn finish(&mut self) {}
+    }
+
+    #[test]
+    fn socket_events() {
+        let writer = SharedWriter::default();
+        let mut socket = ProgressSocket::new(Some(Recorder::default()), Box::new(writer.clone()));
+        socket.start(1024);
+        socket.progress(512);
+        socket.finish();
+
+        let events = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            events.lines().collect::<Vec<_>>(),
+            vec![
+                "{\"event\":\"start\",\"total\":1024}",
+                "{\"event\":\"progress\",\"progress\":512}",
+                "{\"event\":\"finish\"}",
+            ],
+        );
+        assert_eq!(socket.inner.unwrap().0, vec![1024, 512]);
+    }
+
+    #[test]
+    fn socket_disconnected() {
+        let mut socket = ProgressSocket::new(Some(Recorder::default()), Box::new(BrokenWriter));
+        socket.start(1024);
+        assert!(socket.writer.is_none());
+
+        // The inner reporter keeps receiving progress
+        socket.progress(512);
+        socket.finish();
+        assert_eq!(socket.inner.unwrap().0, vec![1024, 512]);
+    }
+
+    /// Create a named pipe in the given directory.
+    #[cfg(unix)]
+    fn mkfifo(dir: &Path) -> std::path::PathBuf {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = dir.join("progress");
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        path
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_fifo_no_reader() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = mkfifo(dir.path());
+        match open_socket(&path) {
+            Err(err) => assert_eq!(err.kind(), ErrorKind::NotConnected),
+            Ok(_) => panic!("expected opening a named pipe without reader to fail"),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_fifo_reader() {
+        use std::io::Read;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = mkfifo(dir.path());
+        let mut reader = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)
+            .unwrap();
+
+        let mut socket = open_socket(&path).unwrap();
+        socket.write_all(b"event\n").unwrap();
+        drop(socket);
+
+        let mut events = String::new();
+        reader.read_to_string(&mut events).unwrap();
+        assert_eq!(events, "event\n");
+    }
+}
@@ -0,0 +1,155 @@
+//! Storage for the secrets of files in history, kept apart from the history file.
+//!
+//! By default, secrets and owner tokens are kept in the history file itself. When compiled with
+//! the `keyring` feature, they are stored in the OS keyring instead, and the history file only
+//! holds non-sensitive metadata.
+
+#[cfg(feature = "keyring")]
+extern crate keyring_rs;
+
+#[cfg(test)]
+use std::cell::RefCell;
+#[cfg(test)]
+use std::collections::HashMap;
+#[cfg(test)]
+use std::rc::Rc;
+
+use failure::Fail;
+
+/// The service name secrets are stored under in the OS keyring.
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "ffsend";
+
+/// The fields of a file in history holding secret material, moved to the secret store.
+pub const SECRET_FIELDS: &[&str] = &["secret", "owner_token"];
+
+/// A store for the secrets of files in history, keyed by file ID.
+///
+/// The secret data of a file is an opaque string, built by the history.
+pub trait SecretStore {
+    /// Store the secret data for the file with the given ID, replacing any existing data.
+    fn store(&self, id: &str, data: &str) -> Result<(), Error>;
+
+    /// Retrieve the secret data for the file with the given ID.
+    ///
+    /// `None` is returned if nothing is stored for the file.
+    fn retrieve(&self, id: &str) -> Result<Option<String>, Error>;
+
+    /// Delete the secret data for the file with the given ID, if there is any.
+    fn delete(&self, id: &str) -> Result<(), Error>;
+}
+
+/// Get the secret store to use for history.
+///
+/// `None` is returned if secrets should be kept in the history file itself, which is the case
+/// unless compiled with the `keyring` feature.
+pub fn default_store() -> Option<Box<dyn SecretStore>> {
+    #[cfg(feature = "keyring")]
+    {
+        Some(Box::new(KeyringStore))
+    }
+    #[cfg(not(feature = "keyring"))]
+    {
+        None
+    }
+}
+
+/// A secret store backed by the OS keyring.
+#[cfg(feature = "keyring")]
+pub struct KeyringStore;
+
+#[cfg(feature = "keyring")]
+impl SecretStore for KeyringStore {
+    fn store(&self, id: &str, data: &str) -> Result<(), Error> {
+        keyring_rs::Keyring::new(KEYRING_SERVICE, id)
+            .set_password(data)
+            .map_err(|err| Error::Backend(err.to_string()))
+    }
+
+    fn retrieve(&self, id: &str) -> Result<Option<String>, Error> {
+        match keyring_rs::Keyring::new(KEYRING_SERVICE, id).get_password() {
+            Ok(data) => Ok(Some(data)),
+            Err(keyring_rs::KeyringError::NoPasswordFound) => Ok(None),
+            Err(err) => Err(Error::Backend(err.to_string())),
+        }
+    }
+
+    fn delete(&self, id: &str) -> Result<(), Error> {
+        match keyring_rs::Keyring::new(KEYRING_SERVICE, id).delete_password() {
+            Ok(()) | Err(keyring_rs::KeyringError::NoPasswordFound) => Ok(()),
+            Err(err) => Err(Error::Backend(err.to_string())),
+        }
+    }
+}
+
+/// A secret store kept in memory, to test with.
+///
+/// Clones share the same stored data.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    /// The stored secret data, by file ID.
+    pub data: Rc<RefCell<HashMap<String, String>>>,
+
+    /// Whether to fail every operation, like an unavailable keyring.
+    pub broken: bool,
+}
+
+#[cfg(test)]
+impl MemoryStore {
+    /// Fail if this store is broken.
+    fn check(&self) -> Result<(), Error> {
+        if self.broken {
+            Err(Error::Backend("store unavailable".into()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+impl SecretStore for MemoryStore {
+    fn store(&self, id: &str, data: &str) -> Result<(), Error> {
+        self.check()?;
+        self.data.borrow_mut().insert(id.into(), data.into());
+        Ok(())
+    }
+
+    fn retrieve(&self, id: &str) -> Result<Option<String>, Error> {
+        self.check()?;
+        Ok(self.data.borrow().get(id).cloned())
+    }
+
+    fn delete(&self, id: &str) -> Result<(), Error> {
+        self.check()?;
+        self.data.borrow_mut().remove(id);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// The secret store backend failed.
+    #[cfg_attr(not(any(feature = "keyring", test)), allow(dead_code))]
+    #[fail(display = "failed to access secret store: {}", _0)]
+    Backend(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_store_retrieve_delete() {
+        let store = MemoryStore::default();
+        assert_eq!(store.retrieve("abc").unwrap(), None);
+
+        store.store("abc", "secret").unwrap();
+        store.store("abc", "replaced").unwrap();
+        assert_eq!(store.retrieve("abc").unwrap(), Some("replaced".into()));
+
+        store.delete("abc").unwrap();
+        store.delete("abc").unwrap();
+        assert_eq!(store.retrieve("abc").unwrap(), None);
+    }
+}
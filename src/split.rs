@@ -0,0 +1,597 @@
+//! Splitting of large files into multiple linked uploads.
+//!
+//! A split file is uploaded as a number of parts, each being a regular Send file. A manifest
+//! listing the share URLs of all parts is uploaded last, and it's share URL is used to download
+//! and reassemble the original file.
+
+extern crate toml;
+
+use std::fs::{self, File};
+use std::io::{self, Error as IoError, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use self::toml::de::Error as DeError;
+use self::toml::ser::Error as SerError;
+use failure::Fail;
+use ffsend_api::action::delete::Delete as ApiDelete;
+use ffsend_api::action::download::{Download as ApiDownload, Error as DownloadError};
+use ffsend_api::action::metadata::MetadataResponse;
+use ffsend_api::action::params::ParamsDataBuilder;
+use ffsend_api::action::upload::{Error as UploadError, Upload as ApiUpload};
+use ffsend_api::api::Version as ApiVersion;
+use ffsend_api::client::{Client, ClientConfig};
+use ffsend_api::file::remote_file::{FileParseError, RemoteFile};
+use ffsend_api::pipe::ProgressReporter;
+use ffsend_api::url::Url;
+use tempfile::{Builder as TempBuilder, NamedTempFile};
+
+use crate::action::retry;
+use crate::cmd::matcher::MainMatcher;
+use crate::host::{check_https, HostError};
+use crate::progress::{watch, StallSignal, WatchError};
+use crate::util::print_error;
+
+/// The file name suffix used for split file manifests.
+pub const MANIFEST_SUFFIX: &str = ".ffsplit";
+
+/// A manifest describing all parts of a split file.
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    /// The name of the original file.
+    name: String,
+
+    /// The size of the original file in bytes.
+    size: u64,
+
+    /// The parts of the file, in order.
+    parts: Vec<Part>,
+}
+
+/// A single part of a split file.
+#[derive(Serialize, Deserialize)]
+pub struct Part {
+    /// The share URL of the part, including it's secret.
+    url: String,
+
+    /// The size of the part in bytes.
+    size: u64,
+}
+
+/// The uploaded files of a split upload.
+pub struct SplitUpload {
+    /// The uploaded manifest, it's share URL is used to download the whole file.
+    pub manifest: RemoteFile,
+
+    /// The uploaded parts, in order. Their owner tokens are required to manage them.
+    pub parts: Vec<RemoteFile>,
+}
+
+impl Manifest {
+    /// The name of the original file.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The size of the original file in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Check whether a file with the given name is a split file manifest.
+pub fn is_manifest(name: &str) -> bool {
+    name.ends_with(MANIFEST_SUFFIX)
+}
+
+/// Upload the file at the given `path` split into parts of at most `part_size` bytes.
+///
+/// Each part is uploaded as separate file, after which a manifest listing all parts is uploaded.
+/// The remote files of the uploaded manifest and parts are returned. If the upload fails, the
+/// parts uploaded so far are deleted again.
+#[allow(clippy::too_many_arguments)]
+pub fn upload(
+    client_config: &ClientConfig,
+    reporter: Option<&Arc<Mutex<dyn ProgressReporter>>>,
+    stall: &StallSignal,
+    api_version: ApiVersion,
+    host: Url,
+    path: &Path,
+    name: String,
+    part_size: u64,
+    password: Option<String>,
+    download_limit: Option<u8>,
+    matcher_main: &MainMatcher,
+) -> Result<SplitUpload, Error> {
+    let mut parts = Vec::new();
+    match upload_parts(
+        client_config,
+        reporter,
+        stall,
+        api_version,
+        host,
+        path,
+        name,
+        part_size,
+        password,
+        download_limit,
+        matcher_main,
+        &mut parts,
+    ) {
+        Ok(manifest) => Ok(SplitUpload { manifest, parts }),
+        Err(err) => {
+            delete_parts(&client_config.clone().client(false), &parts);
+            Err(err)
+        }
+    }
+}
+
+/// Upload the parts and manifest of a split file, see `upload`.
+///
+/// Each uploaded part is pushed to `parts` right away, so they can be cleaned up on failure.
+#[allow(clippy::too_many_arguments)]
+fn upload_parts(
+    client_config: &ClientConfig,
+    reporter: Option<&Arc<Mutex<dyn ProgressReporter>>>,
+    stall: &StallSignal,
+    api_version: ApiVersion,
+    host: Url,
+    path: &Path,
+    name: String,
+    part_size: u64,
+    password: Option<String>,
+    download_limit: Option<u8>,
+    matcher_main: &MainMatcher,
+    parts: &mut Vec<RemoteFile>,
+) -> Result<RemoteFile, Error> {
+    let mut source = File::open(path).map_err(Error::Read)?;
+    let mut manifest = Manifest {
+        name: name.clone(),
+        size: 0,
+        parts: Vec::new(),
+    };
+
+    // Copy each part of the file into a temporary file, and upload it
+    while let Some((tmp_part, size)) = next_part(&mut source, part_size)? {
+        if !matcher_main.quiet() {
+            eprintln!("Uploading part {}...", manifest.parts.len() + 1);
+        }
+        let part = upload_file(
+            client_config,
+            reporter,
+            stall,
+            api_version,
+            host.clone(),
+            tmp_part.path().to_path_buf(),
+            format!("{}.part{}", name, manifest.parts.len() + 1),
+            password.clone(),
+            download_limit,
+            matcher_main.retries(),
+        )?;
+        manifest.size += size;
+        manifest.parts.push(Part {
+            url: part.download_url(true).into_string(),
+            size,
+        });
+        parts.push(part);
+    }
+
+    // Write and upload the manifest
+    let tmp_manifest = temp_file()?;
+    fs::write(tmp_manifest.path(), toml::to_string(&manifest)?).map_err(Error::TempFile)?;
+    upload_file(
+        client_config,
+        reporter,
+        stall,
+        api_version,
+        host,
+        tmp_manifest.path().to_path_buf(),
+        format!("{}{}", name, MANIFEST_SUFFIX),
+        password,
+        download_limit,
+        matcher_main.retries(),
+    )
+}
+
+/// Copy the next part of at most `part_size` bytes from `source` into a temporary file.
+///
+/// The temporary file and the size of the part are returned, or `None` if nothing is left.
+fn next_part<R: Read>(
+    source: &mut R,
+    part_size: u64,
+) -> Result<Option<(NamedTempFile, u64)>, Error> {
+    let mut tmp_part = temp_file()?;
+    let size =
+        io::copy(&mut source.take(part_size), tmp_part.as_file_mut()).map_err(Error::Read)?;
+    if size == 0 {
+        return Ok(None);
+    }
+    Ok(Some((tmp_part, size)))
+}
+
+/// Delete the given uploaded parts, to clean up after a failed or aborted split upload.
+///
+/// Failures are reported and otherwise ignored, so deleting all parts is attempted.
+pub fn delete_parts(client: &Client, parts: &[RemoteFile]) {
+    for part in parts {
+        if let Err(err) = ApiDelete::new(part, None).invoke(client) {
+            print_error(err.context("failed to delete uploaded file part, ignoring"));
+        }
+    }
+}
+
+/// Upload a single file, with the given parameters.
+///
+/// The upload is retried up to `retries` times, and is watched for stalls raising `stall`.
+#[allow(clippy::too_many_arguments)]
+fn upload_file(
+    client_config: &ClientConfig,
+    reporter: Option<&Arc<Mutex<dyn ProgressReporter>>>,
+    stall: &StallSignal,
+    api_version: ApiVersion,
+    host: Url,
+    path: PathBuf,
+    name: String,
+    password: Option<String>,
+    download_limit: Option<u8>,
+    retries: u32,
+) -> Result<RemoteFile, Error> {
+    let client_config = client_config.clone();
+    let reporter = reporter.cloned();
+    watch(stall, move || {
+        let client = client_config.client(true);
+        retry(retries, || {
+            let params = ParamsDataBuilder::default()
+                .download_limit(download_limit)
+                .build()
+                .unwrap();
+            let params = if params.is_empty() {
+                None
+            } else {
+                Some(params)
+            };
+
+            ApiUpload::new(
+                api_version,
+                host.clone(),
+                path.clone(),
+                Some(name.clone()),
+                password.clone(),
+                params,
+            )
+            .invoke(&client, reporter.as_ref())
+        })
+        .map_err(Error::from)
+    })
+}
+
+/// Download and parse the manifest of a split file.
+pub fn fetch_manifest(
+    client: &Client,
+    api_version: ApiVersion,
+    file: &RemoteFile,
+    password: Option<String>,
+    metadata: MetadataResponse,
+) -> Result<Manifest, Error> {
+    let tmp_manifest = temp_file()?;
+    ApiDownload::new(
+        api_version,
+        file,
+        tmp_manifest.path().to_path_buf(),
+        password,
+        false,
+        Some(metadata),
+    )
+    .invoke(client, None)?;
+
+    let data = fs::read_to_string(tmp_manifest.path()).map_err(Error::Read)?;
+    toml::from_str(&data).map_err(|err| err.into())
+}
+
+/// Download all parts listed in the given `manifest`, and reassemble them into `output`.
+///
+/// The size of each part and of the reassembled file is verified against the manifest. Parts must
+/// be hosted on the same origin as the manifest at `manifest_url`. If an error is returned, the
+/// output is incomplete and must be discarded.
+#[allow(clippy::too_many_arguments)]
+pub fn download<W: Write>(
+    client_config: &ClientConfig,
+    reporter: Option<Arc<Mutex<dyn ProgressReporter>>>,
+    stall: &StallSignal,
+    api_version: ApiVersion,
+    manifest: &Manifest,
+    manifest_url: &Url,
+    output: &mut W,
+    password: Option<String>,
+    matcher_main: &MainMatcher,
+) -> Result<(), Error> {
+    let mut total = 0;
+
+    for (i, part) in manifest.parts.iter().enumerate() {
+        // Parse the part share URL
+        let url = part_url(manifest_url, &part.url, matcher_main.allow_http())?;
+        let file = RemoteFile::parse_url(url, None)?;
+
+        // Download the part into a temporary file
+        if !matcher_main.quiet() {
+            eprintln!("Downloading part {} of {}...", i + 1, manifest.parts.len());
+        }
+        let tmp_part = temp_file()?;
+        let client_config = client_config.clone();
+        let path = tmp_part.path().to_path_buf();
+        let password = password.clone();
+        let reporter = reporter.clone();
+        let retries = matcher_main.retries();
+        watch(stall, move || {
+            let client = client_config.client(true);
+            retry(retries, || {
+                ApiDownload::new(
+                    api_version,
+                    &file,
+                    path.clone(),
+                    password.clone(),
+                    true,
+                    None,
+                )
+                .invoke(&client, reporter.clone())
+            })
+            .map_err(Error::from)
+        })?;
+
+        // Verify the part size, and append it to the output
+        total += append_part(output, tmp_part.path(), part.size, i + 1)?;
+    }
+
+    // Verify the size of the reassembled file
+    if total != manifest.size {
+        return Err(Error::Size);
+    }
+
+    Ok(())
+}
+
+/// Append the part at `path` to `output`, verifying it has the `expected` size.
+///
+/// The number of appended bytes is returned.
+fn append_part<W: Write>(
+    output: &mut W,
+    path: &Path,
+    expected: u64,
+    index: usize,
+) -> Result<u64, Error> {
+    let mut part_file = File::open(path).map_err(Error::Read)?;
+    let size = io::copy(&mut part_file, output).map_err(Error::Write)?;
+    if size != expected {
+        return Err(Error::PartSize(index));
+    }
+    Ok(size)
+}
+
+/// Parse the share URL of a part listed in a manifest.
+///
+/// A part must be hosted on the same origin as the manifest at `manifest_url`, so a manifest can't
+/// make us fetch files from arbitrary hosts. Plaintext URLs are refused unless `allow_http` is set.
+fn part_url(manifest_url: &Url, url: &str, allow_http: bool) -> Result<Url, Error> {
+    let url = Url::parse(url).map_err(|_| Error::Manifest)?;
+    if url.origin() != manifest_url.origin() {
+        return Err(Error::PartOrigin);
+    }
+    check_https(&url, allow_http).map_err(Error::PartHost)?;
+    Ok(url)
+}
+
+/// Create a new temporary file, used for parts and manifests.
+fn temp_file() -> Result<NamedTempFile, Error> {
+    TempBuilder::new()
+        .prefix(&format!(".{}-split-", crate_name!()))
+        .tempfile()
+        .map_err(Error::TempFile)
+}
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// Failed to create a temporary file for a part or the manifest.
+    #[fail(display = "failed to create temporary file")]
+    TempFile(#[cause] IoError),
+
+    /// Failed to read a file or part.
+    #[fail(display = "failed to read file")]
+    Read(#[cause] IoError),
+
+    /// Failed to write the reassembled file.
+    #[fail(display = "failed to write reassembled file")]
+    Write(#[cause] IoError),
+
+    /// Failed to upload a part or the manifest.
+    #[fail(display = "failed to upload file part")]
+    Upload(#[cause] UploadError),
+
+    /// Failed to download a part or the manifest.
+    #[fail(display = "failed to download file part")]
+    Download(#[cause] DownloadError),
+
+    /// The transfer of a part or the manifest stalled, or ended unexpectedly.
+    #[fail(display = "failed to transfer file part")]
+    Watch(#[cause] WatchError),
+
+    /// Failed to serialize the manifest.
+    #[fail(display = "failed to serialize the split file manifest")]
+    Serialize(#[cause] SerError),
+
+    /// Failed to parse the manifest.
+    #[fail(display = "failed to parse the split file manifest")]
+    Parse(#[cause] DeError),
+
+    /// The manifest contains an invalid part URL.
+    #[fail(display = "invalid part share link in the split file manifest")]
+    Manifest,
+
+    /// A part isn't hosted on the same origin as the manifest.
+    #[fail(display = "a part in the split file manifest is hosted on another host")]
+    PartOrigin,
+
+    /// The host of a part may not be used.
+    #[fail(display = "refusing the host of a part in the split file manifest")]
+    PartHost(#[cause] HostError),
+
+    /// Failed to parse the share URL of a part.
+    #[fail(display = "invalid part share link")]
+    InvalidUrl(#[cause] FileParseError),
+
+    /// The size of a downloaded part doesn't match the manifest.
+    #[fail(display = "the size of part {} doesn't match the manifest", _0)]
+    PartSize(usize),
+
+    /// The size of the reassembled file doesn't match the manifest.
+    #[fail(display = "the size of the reassembled file doesn't match the manifest")]
+    Size,
+}
+
+impl From<UploadError> for Error {
+    fn from(err: UploadError) -> Self {
+        Error::Upload(err)
+    }
+}
+
+impl From<DownloadError> for Error {
+    fn from(err: DownloadError) -> Self {
+        Error::Download(err)
+    }
+}
+
+impl From<WatchError> for Error {
+    fn from(err: WatchError) -> Self {
+        Error::Watch(err)
+    }
+}
+
+impl From<SerError> for Error {
+    fn from(err: SerError) -> Self {
+        Error::Serialize(err)
+    }
+}
+
+impl From<DeError> for Error {
+    fn from(err: DeError) -> Self {
+        Error::Parse(err)
+    }
+}
+
+impl From<FileParseError> for Error {
+    fn from(err: FileParseError) -> Self {
+        Error::InvalidUrl(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    fn manifest_url() -> Url {
+        Url::parse("https://send.example.com/download/abc/#key").unwrap()
+    }
+
+    #[test]
+    fn manifest_round_trip() {
+        let manifest = Manifest {
+            name: "file.bin".into(),
+            size: 5,
+            parts: vec![
+                Part {
+                    url: "https://send.example.com/download/a/#k".into(),
+                    size: 3,
+                },
+                Part {
+                    url: "https://send.example.com/download/b/#k".into(),
+                    size: 2,
+                },
+            ],
+        };
+        let parsed: Manifest = toml::from_str(&toml::to_string(&manifest).unwrap()).unwrap();
+        assert_eq!(parsed.name(), "file.bin");
+        assert_eq!(parsed.size(), 5);
+        assert_eq!(parsed.parts.len(), 2);
+        assert_eq!(parsed.parts[1].url, manifest.parts[1].url);
+        assert_eq!(parsed.parts[1].size, 2);
+    }
+
+    #[test]
+    fn split_and_reassemble() {
+        let data: Vec<u8> = (0..10 * 1024).map(|i| (i % 251) as u8).collect();
+        let mut source = Cursor::new(data.clone());
+
+        let mut parts = Vec::new();
+        while let Some(part) = next_part(&mut source, 3000).unwrap() {
+            parts.push(part);
+        }
+        assert_eq!(
+            parts.iter().map(|(_, size)| *size).collect::<Vec<_>>(),
+            vec![3000, 3000, 3000, 1240],
+        );
+
+        let mut output = Vec::new();
+        for (i, (tmp_part, size)) in parts.iter().enumerate() {
+            append_part(&mut output, tmp_part.path(), *size, i + 1).unwrap();
+        }
+        assert_eq!(output, data);
+    }
+
+    #[test]
+    fn append_part_size_mismatch() {
+        let (tmp_part, _) = next_part(&mut Cursor::new(vec![0; 10]), 100)
+            .unwrap()
+            .unwrap();
+        match append_part(&mut Vec::new(), tmp_part.path(), 11, 2) {
+            Err(Error::PartSize(2)) => {}
+            _ => panic!("expected a part size error"),
+        }
+    }
+
+    #[test]
+    fn part_url_same_origin() {
+        let url = "https://send.example.com/download/def/#key";
+        assert_eq!(part_url(&manifest_url(), url, false).unwrap().as_str(), url);
+    }
+
+    #[test]
+    fn part_url_other_origin() {
+        for url in &[
+            "https://evil.example.com/download/def/#key",
+            "https://send.example.com:8443/download/def/#key",
+            "http://send.example.com/download/def/#key",
+        ] {
+            match part_url(&manifest_url(), url, true) {
+                Err(Error::PartOrigin) => {}
+                _ => panic!("expected an origin error for {}", url),
+            }
+        }
+    }
+
+    #[test]
+    fn part_url_plaintext() {
+        let manifest_url = Url::parse("http://send.example.com/download/abc/#key").unwrap();
+        let url = "http://send.example.com/download/def/#key";
+        match part_url(&manifest_url, url, false) {
+            Err(Error::PartHost(HostError::Plaintext)) => {}
+            _ => panic!("expected a plaintext host error"),
+        }
+        assert!(part_url(&manifest_url, url, true).is_ok());
+    }
+
+    #[test]
+    fn part_url_invalid() {
+        match part_url(&manifest_url(), "not a url", false) {
+            Err(Error::Manifest) => {}
+            _ => panic!("expected a manifest error"),
+        }
+    }
+
+    #[test]
+    fn manifest_names() {
+        assert!(is_manifest("file.bin.ffsplit"));
+        assert!(!is_manifest("file.bin"));
+        assert!(!is_manifest("file.ffsplit.bin"));
+    }
+}
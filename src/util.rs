@@ -16,18 +16,19 @@ use std::fmt::{Debug, Display};
 #[cfg(feature = "clipboard-bin")]
 use std::io::ErrorKind as IoErrorKind;
 use std::io::{stderr, stdin, Error as IoError, Write};
-use std::path::Path;
 use std::path::PathBuf;
+use std::path::{self, Path};
 use std::process::{exit, ExitStatus};
 #[cfg(feature = "clipboard-bin")]
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 #[cfg(feature = "clipboard-crate")]
 use self::clip::{ClipboardContext, ClipboardProvider};
 use self::colored::*;
-#[cfg(feature = "history")]
 use self::directories::ProjectDirs;
 use self::fs2::available_space;
+use atty::Stream;
 use chrono::Duration;
 use failure::{err_msg, Fail};
 #[cfg(feature = "clipboard-crate")]
@@ -39,19 +40,52 @@ use ffsend_api::{
     url::Url,
 };
 use rpassword::prompt_password_stderr;
+use serde::Serialize;
+use serde_json::json;
 #[cfg(feature = "clipboard-bin")]
 use which::which;
 
 use crate::cmd::matcher::MainMatcher;
+use crate::host::{check_https, HostError};
+use crate::metrics;
 
 /// Print a success message.
 pub fn print_success(msg: &str) {
     eprintln!("{}", msg.green());
 }
 
+/// Whether to emit machine-readable JSON output, instead of output for humans.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Set whether to emit machine-readable JSON output.
+///
+/// When enabled, errors are also reported as JSON objects.
+pub fn set_json_output(json: bool) {
+    JSON_OUTPUT.store(json, Ordering::Relaxed);
+}
+
+/// Print the given value as JSON object on a single line to stdout.
+pub fn print_json<T: Serialize>(value: &T) {
+    match serde_json::to_string(value) {
+        Ok(json) => println!("{}", json),
+        Err(err) => quit_error(
+            err.context("failed to serialize JSON output"),
+            ErrorHints::default(),
+        ),
+    }
+}
+
 /// Print the given error in a proper format for the user,
 /// with it's causes.
+///
+/// In JSON output mode, the error and it's causes are printed as JSON object instead.
 pub fn print_error<E: Fail>(err: impl Borrow<E>) {
+    // Report the error as JSON object
+    if JSON_OUTPUT.load(Ordering::Relaxed) {
+        eprintln!("{}", error_json(err.borrow()));
+        return;
+    }
+
     // Report each printable error, count them
     let count = err
         .borrow()
@@ -78,6 +112,19 @@ pub fn print_error<E: Fail>(err: impl Borrow<E>) {
     }
 }
 
+/// Build the JSON object to report the given error and it's causes with in JSON output mode.
+fn error_json<E: Fail>(err: &E) -> serde_json::Value {
+    let mut causes = err
+        .causes()
+        .map(|err| format!("{}", err))
+        .filter(|err| !err.is_empty());
+    let error = causes
+        .next()
+        .unwrap_or_else(|| "an undefined error occurred".into());
+    let causes: Vec<String> = causes.collect();
+    json!({ "error": error, "causes": causes })
+}
+
 /// Print the given error message in a proper format for the user,
 /// with it's causes.
 pub fn print_error_msg<S>(err: S)
@@ -97,6 +144,7 @@ where
 
 /// Quit the application regularly.
 pub fn quit() -> ! {
+    metrics::flush();
     exit(0);
 }
 
@@ -106,10 +154,13 @@ pub fn quit_error<E: Fail>(err: E, hints: impl Borrow<ErrorHints>) -> ! {
     // Print the error
     print_error(err);
 
-    // Print error hints
-    hints.borrow().print();
+    // Print error hints, not in JSON output mode
+    if !JSON_OUTPUT.load(Ordering::Relaxed) {
+        hints.borrow().print();
+    }
 
     // Quit
+    metrics::flush();
     exit(1);
 }
 
@@ -532,6 +583,14 @@ pub fn prompt_password(main_matcher: &MainMatcher, optional: bool) -> Option<Str
         );
     }
 
+    // Without a terminal to read from, skip an optional password or quit with an error
+    if let Err(err) = check_tty("Password", atty::is(Stream::Stdin)) {
+        if optional {
+            return None;
+        }
+        quit_error_msg(err, ErrorHints::default());
+    }
+
     // Prompt for the password
     let prompt = if optional {
         "Password (optional): "
@@ -562,6 +621,51 @@ pub fn prompt_password(main_matcher: &MainMatcher, optional: bool) -> Option<Str
     }
 }
 
+/// Prompt the user to enter a new password, and to enter it again to confirm.
+///
+/// The given `prompt` is shown first. The program quits with an error if the passwords don't
+/// match, or if we may not interact.
+pub fn prompt_new_password(main_matcher: &MainMatcher, prompt: &str) -> String {
+    // Quit with an error if we may not interact
+    if main_matcher.no_interact() {
+        quit_error_msg(
+            "missing password, must be specified in no-interact mode",
+            ErrorHintsBuilder::default()
+                .password(true)
+                .verbose(false)
+                .build()
+                .unwrap(),
+        );
+    }
+
+    // Quit with an error if there is no terminal to read the password from
+    if let Err(err) = check_tty("Password", atty::is(Stream::Stdin)) {
+        quit_error_msg(err, ErrorHints::default());
+    }
+
+    // Prompt for the password twice
+    let password = prompt_password_stderr(prompt).unwrap_or_else(|err| {
+        quit_error(
+            err.context("failed to read password from password prompt"),
+            ErrorHints::default(),
+        )
+    });
+    let confirm = prompt_password_stderr("Confirm password: ").unwrap_or_else(|err| {
+        quit_error(
+            err.context("failed to read password from password prompt"),
+            ErrorHints::default(),
+        )
+    });
+    if password != confirm {
+        quit_error_msg(
+            "the passwords don't match",
+            ErrorHintsBuilder::default().verbose(false).build().unwrap(),
+        );
+    }
+
+    password
+}
+
 /// Get a password if required.
 /// This method will ensure a password is set (or not) in the given `password`
 /// parameter, as defined by `needs`.
@@ -634,6 +738,11 @@ pub fn prompt(msg: &str, main_matcher: &MainMatcher) -> String {
         );
     }
 
+    // Quit with an error if there is no terminal to read the answer from
+    if let Err(err) = check_tty(msg, atty::is(Stream::Stdin)) {
+        quit_error_msg(err, ErrorHints::default());
+    }
+
     // Show the prompt
     eprint!("{}: ", msg);
     let _ = stderr().flush();
@@ -651,6 +760,20 @@ pub fn prompt(msg: &str, main_matcher: &MainMatcher) -> String {
     input.trim().to_owned()
 }
 
+/// Check whether we can prompt for `msg`, given whether stdin is a terminal.
+///
+/// Prompting without a terminal would block or read garbage when running in a pipeline or CI.
+fn check_tty(msg: &str, tty: bool) -> Result<(), String> {
+    if tty {
+        Ok(())
+    } else {
+        Err(format!(
+            "could not prompt for '{}', no TTY; pass --password/--yes",
+            msg
+        ))
+    }
+}
+
 /// Prompt the user for a question, allowing a yes or now answer.
 /// True is returned if yes was answered, false if no.
 ///
@@ -811,19 +934,89 @@ pub fn ensure_owner_token(
     }
 }
 
+/// Whether to format sizes in SI units, being powers of 1000.
+static SI_UNITS: AtomicBool = AtomicBool::new(false);
+
+/// Set whether to format sizes in SI units, being powers of 1000, rather than powers of 1024.
+pub fn set_si_units(si: bool) {
+    SI_UNITS.store(si, Ordering::Relaxed);
+}
+
 /// Format the given number of bytes readable for humans.
+///
+/// IEC units being powers of 1024 are used, unless SI units are enabled with `set_si_units`.
 pub fn format_bytes(bytes: u64) -> String {
+    format_bytes_units(bytes, SI_UNITS.load(Ordering::Relaxed))
+}
+
+/// Format the given number of bytes readable for humans, see `format_bytes`.
+///
+/// SI units are used if `si` is set, IEC units otherwise.
+fn format_bytes_units(bytes: u64, si: bool) -> String {
     let bytes = bytes as f64;
-    let kb = 1024f64;
+    let (kb, units) = if si {
+        (1000f64, ["kB", "MB", "GB", "TB"])
+    } else {
+        (1024f64, ["KiB", "MiB", "GiB", "TiB"])
+    };
     match bytes {
-        bytes if bytes >= kb.powf(4_f64) => format!("{:.*} TiB", 2, bytes / kb.powf(4_f64)),
-        bytes if bytes >= kb.powf(3_f64) => format!("{:.*} GiB", 2, bytes / kb.powf(3_f64)),
-        bytes if bytes >= kb.powf(2_f64) => format!("{:.*} MiB", 2, bytes / kb.powf(2_f64)),
-        bytes if bytes >= kb => format!("{:.*} KiB", 2, bytes / kb),
+        bytes if bytes >= kb.powf(4_f64) => {
+            format!("{:.*} {}", 2, bytes / kb.powf(4_f64), units[3])
+        }
+        bytes if bytes >= kb.powf(3_f64) => {
+            format!("{:.*} {}", 2, bytes / kb.powf(3_f64), units[2])
+        }
+        bytes if bytes >= kb.powf(2_f64) => {
+            format!("{:.*} {}", 2, bytes / kb.powf(2_f64), units[1])
+        }
+        bytes if bytes >= kb => format!("{:.*} {}", 2, bytes / kb, units[0]),
         _ => format!("{:.*} B", 0, bytes),
     }
 }
 
+/// Parse the given human readable size, such as `500M` or `2GiB`, into a number of bytes.
+///
+/// SI units such as `kB` and `MB` are 1000 based, IEC units such as `KiB` and `MiB` are 1024
+/// based, regardless of case. Bare units such as `K` and `M` are 1024 based, unless SI units are
+/// enabled with `set_si_units`, to match how sizes are shown. A plain number is regarded as bytes.
+/// `None` is returned if the size could not be parsed.
+pub fn parse_size(size: &str) -> Option<u64> {
+    parse_size_units(size, SI_UNITS.load(Ordering::Relaxed))
+}
+
+/// Parse the given human readable size, see `parse_size`.
+///
+/// If `si` is set, bare units are 1000 based rather than 1024 based.
+fn parse_size_units(size: &str, si: bool) -> Option<u64> {
+    // Split the number from the unit
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or_else(|| size.len());
+    let (number, unit) = size.split_at(split);
+
+    // Determine the unit multiplier
+    let bare: u64 = if si { 1000 } else { 1024 };
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" => bare,
+        "m" => bare.pow(2),
+        "g" => bare.pow(3),
+        "t" => bare.pow(4),
+        "kb" => 1000,
+        "mb" => 1000u64.pow(2),
+        "gb" => 1000u64.pow(3),
+        "tb" => 1000u64.pow(4),
+        "kib" => 1024,
+        "mib" => 1024u64.pow(2),
+        "gib" => 1024u64.pow(3),
+        "tib" => 1024u64.pow(4),
+        _ => return None,
+    };
+
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
 /// Format the given duration in a human readable format.
 /// This method builds a string of time components to represent
 /// the given duration.
@@ -874,6 +1067,65 @@ pub fn format_duration(duration: impl Borrow<Duration>) -> String {
     components.join("")
 }
 
+/// Sanitize the given value for use as single path component.
+///
+/// Path separators of any platform, drive separators (`:`) and control characters are replaced,
+/// and special directory names are neutralized. Reserved Windows device names such as `CON` or
+/// `nul.txt` are prefixed with an underscore.
+pub fn sanitize_component(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .map(|c| {
+            if path::is_separator(c) || c == '/' || c == '\\' || c == ':' || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    match value.trim() {
+        "" | "." | ".." => "_".into(),
+        _ if is_reserved_name(&value) => format!("_{}", value),
+        _ => value,
+    }
+}
+
+/// Check whether the given file name is a reserved device name on Windows.
+///
+/// Such names are reserved regardless of case or extension, such as `con` or `NUL.txt`.
+fn is_reserved_name(name: &str) -> bool {
+    let stem = name
+        .split('.')
+        .next()
+        .unwrap_or("")
+        .trim_end()
+        .to_uppercase();
+    match stem.as_str() {
+        "CON" | "PRN" | "AUX" | "NUL" | "CONIN$" | "CONOUT$" => true,
+        _ => {
+            (stem.starts_with("COM") || stem.starts_with("LPT"))
+                && stem.len() == 4
+                && stem[3..].chars().all(|c| c.is_ascii_digit() && c != '0')
+        }
+    }
+}
+
+/// Escape the given text for use in HTML, including attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 /// Format the given boolean, as `yes` or `no`.
 pub fn format_bool(b: bool) -> &'static str {
     if b {
@@ -911,19 +1163,11 @@ pub fn bin_name() -> String {
 /// If there is not enough disk space available,
 /// an error is reported and the program will quit.
 pub fn ensure_enough_space<P: AsRef<Path>>(path: P, size: u64) {
-    // Get the available space at this path
-    let space = match available_space(path) {
-        Ok(space) => space,
-        Err(err) => {
-            print_error(err.context("failed to check available space on disk, ignoring"));
-            return;
-        }
-    };
-
     // Return if enough disk space is avaiable
-    if space >= size {
-        return;
-    }
+    let space = match check_enough_space(path, size) {
+        Ok(()) => return,
+        Err(space) => space,
+    };
 
     // Create an info message giving details about the required space
     let info = format!(
@@ -945,6 +1189,22 @@ pub fn ensure_enough_space<P: AsRef<Path>>(path: P, size: u64) {
     );
 }
 
+/// Check whether there is enough free disk space available at the given `path`,
+/// to store a file with the given `size`.
+///
+/// If there isn't, the available space is returned as error. If an error occurred while querying
+/// the file system, the error is reported to the user and the check passes.
+pub fn check_enough_space<P: AsRef<Path>>(path: P, size: u64) -> Result<(), u64> {
+    match available_space(path) {
+        Ok(space) if space < size => Err(space),
+        Ok(_) => Ok(()),
+        Err(err) => {
+            print_error(err.context("failed to check available space on disk, ignoring"));
+            Ok(())
+        }
+    }
+}
+
 /// Get the project directories instance for this application.
 /// This may be used to determine the project, cache, configuration, data and
 /// some other directory paths.
@@ -954,6 +1214,13 @@ pub fn app_project_dirs() -> ProjectDirs {
         .expect("failed to determine location of project directories")
 }
 
+/// Get the path of the default configuration file, which is used if it exists.
+///
+/// `None` is returned if the configuration directory couldn't be determined.
+pub fn app_config_file_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", crate_name!()).map(|dirs| dirs.config_dir().join("config.toml"))
+}
+
 /// Get the default path to use for the history file.
 #[cfg(feature = "history")]
 pub fn app_history_file_path() -> PathBuf {
@@ -990,6 +1257,8 @@ pub fn features_list() -> Vec<&'static str> {
     features.push("clipboard-crate");
     #[cfg(feature = "history")]
     features.push("history");
+    #[cfg(feature = "keyring")]
+    features.push("keyring");
     #[cfg(feature = "qrcode")]
     features.push("qrcode");
     #[cfg(feature = "urlshorten")]
@@ -1023,10 +1292,11 @@ pub fn api_version_list() -> Vec<&'static str> {
 
 /// Follow redirects on the given URL, and return the final full URL.
 ///
-/// This is used to obtain share URLs from shortened links.
+/// This is used to obtain share URLs from shortened links. Like any share URL, the final URL may
+/// only use plaintext HTTP if `allow_http` is set.
 ///
 // TODO: extract this into module
-pub fn follow_url(client: &Client, url: &Url) -> Result<Url, FollowError> {
+pub fn follow_url(client: &Client, url: &Url, allow_http: bool) -> Result<Url, FollowError> {
     // Send the request, follow the URL, ensure success
     let response = client
         .get(url.as_str())
@@ -1034,8 +1304,10 @@ pub fn follow_url(client: &Client, url: &Url) -> Result<Url, FollowError> {
         .map_err(FollowError::Request)?;
     ensure_success(&response)?;
 
-    // Obtain the final URL
-    Ok(response.url().clone())
+    // Obtain the final URL, ensure it may be used
+    let url = response.url().clone();
+    check_https(&url, allow_http).map_err(FollowError::Host)?;
+    Ok(url)
 }
 
 /// URL following error.
@@ -1048,6 +1320,10 @@ pub enum FollowError {
     /// The server responded with a bad response.
     #[fail(display = "failed to shorten URL, got bad response")]
     Response(#[cause] ResponseError),
+
+    /// The URL redirected to a host that may not be used.
+    #[fail(display = "refusing the URL the share URL redirected to")]
+    Host(#[cause] HostError),
 }
 
 impl From<ResponseError> for FollowError {
@@ -1055,3 +1331,197 @@ impl From<ResponseError> for FollowError {
         FollowError::Response(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::ErrorKind;
+
+    #[test]
+    fn sanitize_separators() {
+        assert_eq!(sanitize_component("../../etc/passwd"), ".._.._etc_passwd");
+        assert_eq!(sanitize_component("..\\..\\boot.ini"), ".._.._boot.ini");
+        assert_eq!(sanitize_component("/etc/passwd"), "_etc_passwd");
+        assert_eq!(sanitize_component("a\u{0}b\nc"), "a_b_c");
+    }
+
+    #[test]
+    fn sanitize_special_dirs() {
+        assert_eq!(sanitize_component(""), "_");
+        assert_eq!(sanitize_component("."), "_");
+        assert_eq!(sanitize_component(".."), "_");
+        assert_eq!(sanitize_component(" .. "), "_");
+    }
+
+    #[test]
+    fn sanitize_drive_prefixes() {
+        assert_eq!(sanitize_component("C:"), "C_");
+        assert_eq!(
+            sanitize_component("C:\\Windows\\win.ini"),
+            "C__Windows_win.ini"
+        );
+        assert_eq!(sanitize_component("file:stream"), "file_stream");
+    }
+
+    #[test]
+    fn sanitize_reserved_names() {
+        assert_eq!(sanitize_component("CON"), "_CON");
+        assert_eq!(sanitize_component("nul.txt"), "_nul.txt");
+        assert_eq!(sanitize_component("Com1.tar.gz"), "_Com1.tar.gz");
+        assert_eq!(sanitize_component("LPT9"), "_LPT9");
+        assert_eq!(sanitize_component("aux .txt"), "_aux .txt");
+    }
+
+    #[test]
+    fn sanitize_regular_names() {
+        for name in &[
+            "file.txt",
+            ".bashrc",
+            "CONTRACT.pdf",
+            "COM0",
+            "LPT10",
+            "console",
+        ] {
+            assert_eq!(&sanitize_component(name), name);
+        }
+    }
+
+    #[test]
+    fn check_tty_terminal() {
+        assert!(check_tty("Password", true).is_ok());
+    }
+
+    #[test]
+    fn check_tty_no_terminal() {
+        let err = check_tty("Password", false).unwrap_err();
+        assert!(err.contains("'Password'"));
+        assert!(err.contains("no TTY; pass --password/--yes"));
+    }
+
+    #[test]
+    fn parse_size_units_bytes() {
+        assert_eq!(parse_size_units("0", false), Some(0));
+        assert_eq!(parse_size_units("1", false), Some(1));
+        assert_eq!(parse_size_units(" 42 B ", false), Some(42));
+        assert_eq!(
+            parse_size_units("18446744073709551615", false),
+            Some(u64::max_value())
+        );
+        assert_eq!(parse_size_units("18446744073709551616", false), None);
+    }
+
+    #[test]
+    fn parse_size_units_si_and_iec() {
+        assert_eq!(parse_size_units("1kB", false), Some(1000));
+        assert_eq!(parse_size_units("1KiB", false), Some(1024));
+        assert_eq!(parse_size_units("500MB", false), Some(500_000_000));
+        assert_eq!(parse_size_units("500MiB", false), Some(500 * 1024 * 1024));
+        assert_eq!(parse_size_units("2gb", true), Some(2_000_000_000));
+        assert_eq!(parse_size_units("2gib", true), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size_units("1TB", false), Some(1_000_000_000_000));
+        assert_eq!(parse_size_units("1TiB", false), Some(1 << 40));
+    }
+
+    #[test]
+    fn parse_size_units_bare() {
+        assert_eq!(parse_size_units("1K", false), Some(1024));
+        assert_eq!(parse_size_units("1K", true), Some(1000));
+        assert_eq!(parse_size_units("3M", false), Some(3 << 20));
+        assert_eq!(parse_size_units("3M", true), Some(3_000_000));
+    }
+
+    #[test]
+    fn parse_size_units_overflow() {
+        assert_eq!(
+            parse_size_units("17179869183GiB", false),
+            Some(u64::max_value() - (1 << 30) + 1)
+        );
+        assert_eq!(parse_size_units("17179869184GiB", false), None);
+        assert_eq!(parse_size_units("18446744073709552kB", false), None);
+    }
+
+    #[test]
+    fn parse_size_units_invalid() {
+        for size in &["", "M", "-1M", "1.5G", "1 PB", "1MiBs", "ten"] {
+            assert_eq!(parse_size_units(size, false), None, "{:?}", size);
+        }
+    }
+
+    /// An error with a cause, to test error reporting with.
+    #[derive(Debug, Fail)]
+    enum TestError {
+        #[fail(display = "failed to upload file")]
+        Upload(#[cause] IoError),
+
+        #[fail(display = "")]
+        Silent,
+    }
+
+    #[test]
+    fn error_json_causes() {
+        let err = TestError::Upload(IoError::new(ErrorKind::Other, "connection reset"));
+        assert_eq!(
+            error_json(&err),
+            json!({
+                "error": "failed to upload file",
+                "causes": ["connection reset"],
+            }),
+        );
+    }
+
+    #[test]
+    fn error_json_undefined() {
+        assert_eq!(
+            error_json(&TestError::Silent),
+            json!({ "error": "an undefined error occurred", "causes": [] }),
+        );
+    }
+
+    #[test]
+    fn format_bytes_iec() {
+        assert_eq!(format_bytes_units(0, false), "0 B");
+        assert_eq!(format_bytes_units(1023, false), "1023 B");
+        assert_eq!(format_bytes_units(1024, false), "1.00 KiB");
+        assert_eq!(format_bytes_units(1536, false), "1.50 KiB");
+        assert_eq!(format_bytes_units(5 << 20, false), "5.00 MiB");
+        assert_eq!(format_bytes_units(3 << 30, false), "3.00 GiB");
+        assert_eq!(format_bytes_units(2 << 40, false), "2.00 TiB");
+    }
+
+    #[test]
+    fn format_bytes_si() {
+        assert_eq!(format_bytes_units(999, true), "999 B");
+        assert_eq!(format_bytes_units(1000, true), "1.00 kB");
+        assert_eq!(format_bytes_units(1024, true), "1.02 kB");
+        assert_eq!(format_bytes_units(2_500_000, true), "2.50 MB");
+        assert_eq!(format_bytes_units(7_000_000_000, true), "7.00 GB");
+        assert_eq!(format_bytes_units(1_000_000_000_000, true), "1.00 TB");
+    }
+
+    #[test]
+    fn format_parse_bytes_roundtrip() {
+        for si in &[false, true] {
+            for size in &["1K", "5M", "3G"] {
+                let bytes = parse_size_units(size, *si).unwrap();
+                let formatted = format_bytes_units(bytes, *si);
+                assert!(
+                    formatted.starts_with(&size[..1]),
+                    "{} -> {}",
+                    size,
+                    formatted
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn escape_html_special() {
+        assert_eq!(escape_html("plain text"), "plain text");
+        assert_eq!(
+            escape_html("<script>alert('x') & \"y\"</script>"),
+            "&lt;script&gt;alert(&#39;x&#39;) &amp; &quot;y&quot;&lt;/script&gt;",
+        );
+        assert_eq!(escape_html("&amp;"), "&amp;amp;");
+    }
+}
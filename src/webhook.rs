@@ -0,0 +1,132 @@
+//! Webhook notifications for uploaded files.
+
+use ffsend_api::{
+    api::request::{ensure_success, ResponseError},
+    client::Client,
+    file::remote_file::RemoteFile,
+    reqwest,
+    url::Url,
+};
+
+/// The payload that is posted to a webhook after a file is uploaded.
+#[derive(Serialize)]
+struct Payload<'a> {
+    /// The file ID.
+    id: &'a str,
+
+    /// The share URL, including the secret.
+    url: &'a str,
+
+    /// The number of seconds until the file expires.
+    expiry: i64,
+
+    /// The file size in bytes, if known.
+    size: Option<u64>,
+
+    /// The owner token, only included when explicitly requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner_token: Option<&'a str>,
+}
+
+impl<'a> Payload<'a> {
+    /// Build the payload for the given `file`, shared at `url`.
+    ///
+    /// The owner token is only included if `owner_token` is set.
+    fn new(file: &'a RemoteFile, url: &'a Url, size: Option<u64>, owner_token: bool) -> Self {
+        Payload {
+            id: file.id(),
+            url: url.as_str(),
+            expiry: file.expire_duration().num_seconds(),
+            size,
+            owner_token: if owner_token {
+                file.owner_token().map(|t| t.as_str())
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Notify the given webhook of the uploaded `file`, by posting a JSON payload to it.
+///
+/// The owner token is only included in the payload if `owner_token` is set.
+pub fn notify(
+    client: &Client,
+    webhook: &Url,
+    file: &RemoteFile,
+    size: Option<u64>,
+    owner_token: bool,
+) -> Result<(), Error> {
+    let url = file.download_url(true);
+    let payload = Payload::new(file, &url, size, owner_token);
+
+    // Send the request, ensure success
+    let response = client
+        .post(webhook.as_str())
+        .json(&payload)
+        .send()
+        .map_err(Error::Request)?;
+    ensure_success(&response)?;
+
+    Ok(())
+}
+
+/// A webhook notification error.
+#[derive(Debug, Fail)]
+pub enum Error {
+    /// Failed to send the webhook request.
+    #[fail(display = "failed to send webhook request")]
+    Request(#[cause] reqwest::Error),
+
+    /// The webhook responded with a bad response.
+    #[fail(display = "failed to notify webhook, got bad response")]
+    Response(#[cause] ResponseError),
+}
+
+impl From<ResponseError> for Error {
+    fn from(err: ResponseError) -> Self {
+        Error::Response(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::Value;
+
+    /// Build an uploaded file, with an owner token.
+    fn file() -> RemoteFile {
+        let url = Url::parse("https://send.example.com/download/abc12345/#c2VjcmV0").unwrap();
+        RemoteFile::parse_url(url, Some("token123".into())).unwrap()
+    }
+
+    /// Serialize the payload for the given file to a JSON value.
+    fn payload(file: &RemoteFile, size: Option<u64>, owner_token: bool) -> Value {
+        let url = file.download_url(true);
+        serde_json::to_value(Payload::new(file, &url, size, owner_token)).unwrap()
+    }
+
+    #[test]
+    fn payload_fields() {
+        let payload = payload(&file(), Some(1024), false);
+        assert_eq!(payload["id"], "abc12345");
+        assert_eq!(
+            payload["url"],
+            "https://send.example.com/download/abc12345/#c2VjcmV0"
+        );
+        assert_eq!(payload["size"], 1024);
+        assert!(payload["expiry"].is_i64());
+    }
+
+    #[test]
+    fn payload_owner_token() {
+        assert!(payload(&file(), None, false).get("owner_token").is_none());
+        assert_eq!(payload(&file(), None, true)["owner_token"], "token123");
+    }
+
+    #[test]
+    fn payload_unknown_size() {
+        assert!(payload(&file(), None, false)["size"].is_null());
+    }
+}